@@ -1,49 +1,606 @@
 use crate::config::Config;
-use crate::repository::Repository;
+use crate::import::{self, ImportFormat, ImportedRepository};
+use crate::open::{open_in_editor, open_with_system};
+use crate::report::ErrorReport;
+use crate::repository::{
+    self, BisectStep, BranchSwitch, Distance, EncodingIssue, NamingStrategy, Repository, TimeKind,
+};
+use crate::tui;
 
-use std::collections::BTreeMap;
-use std::path::PathBuf;
-use std::sync::mpsc::channel;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
-use indicatif::{ProgressBar, ProgressStyle};
-use prettytable::{cell, format, row, Table};
+use anyhow::{anyhow, Context, Result};
+use chrono::{Local, TimeZone, Utc};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use notify::{EventKind, RecursiveMode, Watcher};
+use prettytable::{cell, color, format, row, Attr, Cell, Row, Table};
+use structopt::clap::Shell;
 use structopt::StructOpt;
 use threadpool::ThreadPool;
 
 #[derive(StructOpt)]
 #[structopt(about)]
-pub struct CLI {
+pub struct Cli {
     /// Set a custom config file
     #[structopt(value_name = "FILE", short, long, default_value)]
     config: Config,
 
+    /// Combine the config with the one of a profile
+    #[structopt(value_name = "PROFILE", short, long)]
+    profile: Option<String>,
+
+    /// Exit with an error if a repository is not on its expected branch
+    #[structopt(long)]
+    check: bool,
+
     /// Do not fetch
     #[structopt(short = "F", long)]
     no_fetch: bool,
 
-    /// Number of workers
-    #[structopt(value_name = "NUM_WORKERS", short, long, default_value = "4")]
-    workers: usize,
+    /// Keep running and refresh repositories whose files change
+    #[structopt(long)]
+    watch_paths: bool,
+
+    /// Interval in seconds at which to poll repositories which cannot be watched
+    #[structopt(value_name = "SECONDS", long, default_value = "5")]
+    interval: u64,
+
+    /// Timestamp used for the age of the last commit
+    #[structopt(
+        value_name = "KIND",
+        long,
+        default_value = "committer",
+        possible_values = &["author", "committer"]
+    )]
+    commit_time: TimeKind,
+
+    /// Also fetch Git LFS objects of repositories using it
+    #[structopt(long, conflicts_with = "no-fetch")]
+    fetch_lfs: bool,
+
+    /// Fetch with the history truncated to N commits
+    #[structopt(value_name = "N", long, conflicts_with_all = &["no-fetch", "unshallow"])]
+    deepen: Option<u32>,
+
+    /// Fetch the complete history of shallow repositories
+    #[structopt(long, conflicts_with = "no-fetch")]
+    unshallow: bool,
+
+    /// Remove repositories whose path cannot be reached from the config first
+    #[structopt(long, conflicts_with = "profile")]
+    prune_unreachable: bool,
+
+    /// Show the size of the files at HEAD
+    #[structopt(long)]
+    show_tree_size: bool,
+
+    /// Show the number of ignored files
+    #[structopt(long)]
+    show_ignored: bool,
+
+    /// Show the number of binary files in the status
+    #[structopt(long)]
+    check_binary: bool,
+
+    /// Show the number of text files with CRLF line endings in the status
+    #[structopt(long)]
+    check_eol: bool,
+
+    /// Show the number of recent commits adding large files in the status
+    #[structopt(long)]
+    check_large_commits: bool,
+
+    /// Mark the status of repositories with recent commit messages which are not valid UTF-8
+    #[structopt(long)]
+    check_encoding: bool,
+
+    /// Order of the repositories: by name, largest, branch, behind first, dirty first or most
+    /// recently committed first
+    #[structopt(
+        value_name = "KEY",
+        long,
+        default_value = "name",
+        possible_values = &["name", "tree-size", "branch", "distance", "status", "age"]
+    )]
+    sort_by: SortKey,
+
+    /// Reverse the order of the repositories
+    #[structopt(long)]
+    reverse: bool,
+
+    /// Hide the repositories which are clean and in sync with their upstream
+    #[structopt(long)]
+    dirty: bool,
+
+    /// Show distances to the upstream as symbols instead of numbers of commits
+    #[structopt(long)]
+    compact_distance: bool,
+
+    /// Print the row of each repository as soon as it is processed, in no particular order
+    #[structopt(long, conflicts_with_all = &["template", "format", "watch-paths"])]
+    stream: bool,
+
+    /// Only show the repositories behind their upstream
+    #[structopt(long)]
+    behind: bool,
+
+    /// Only show the repositories ahead of their upstream
+    #[structopt(long)]
+    ahead: bool,
+
+    /// Only show the repositories which diverged from their upstream
+    #[structopt(long)]
+    diverged: bool,
+
+    /// Only process the repositories with any of these tags, e.g. "work,oss"
+    #[structopt(
+        value_name = "TAG",
+        long = "tag",
+        use_delimiter = true,
+        number_of_values = 1
+    )]
+    tags: Vec<String>,
+
+    /// Format of the status of the repositories
+    #[structopt(
+        value_name = "FORMAT",
+        long,
+        default_value = "table",
+        possible_values = &["table", "json", "csv", "tsv"]
+    )]
+    format: OutputFormat,
+
+    /// Print each repository with a template instead, e.g. "{name} {branch} {distance}"
+    /// (fields: name, path, branch, status, distance, remote, age, summary)
+    #[structopt(value_name = "TEMPLATE", long)]
+    template: Option<Template>,
+
+    /// Comma-separated columns of the table (name, status, branch, distance, remote, age,
+    /// stashes, tree-size, ignored, summary)
+    #[structopt(value_name = "COLUMNS", long, use_delimiter = true)]
+    columns: Vec<Column>,
+
+    /// When to color the table, `auto` disabling colors if NO_COLOR is set or the output is
+    /// not a terminal
+    #[structopt(
+        value_name = "WHEN",
+        long,
+        default_value = "auto",
+        possible_values = &["auto", "always", "never"]
+    )]
+    color: ColorChoice,
+
+    /// Number of workers [default: 4]
+    #[structopt(value_name = "NUM_WORKERS", short, long)]
+    workers: Option<usize>,
 
     #[structopt(subcommand)]
     command: Option<Command>,
 }
 
+const MEGABYTE: u64 = 1024 * 1024;
+
+/// Size above which a commit is counted as large in the status column
+const LARGE_COMMIT_THRESHOLD_MB: u64 = 10;
+
+/// How often a cancelled processing of the repositories notices it
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Branches never deleted by `glim merged-branches --delete`
+const PROTECTED_BRANCHES: &[&str] = &["main", "master", "develop"];
+
+/// Subcommands whose positional arguments are completed with the names of the repositories
+const NAME_COMMANDS: &[&str] = &[
+    "remove",
+    "rename",
+    "status",
+    "open",
+    "path",
+    "checkout",
+    "fetch",
+    "pull",
+    "sync",
+    "push",
+    "file-log",
+    "sign",
+    "health",
+    "fsck",
+    "count-range",
+    "stage-all",
+    "commit",
+    "hook-log",
+    "fetch-tag",
+    "push-tags",
+    "fix-email",
+    "find-file",
+    "patch",
+    "merged-branches",
+    "ping",
+    "large-commits",
+    "recent-contributors",
+    "fetch-pr",
+    "fetch-branch",
+    "config-unset",
+    "restore",
+    "object-counts",
+    "check-eol",
+    "push-branch",
+    "sync-fork",
+    "unstage",
+    "ignored",
+    "stats",
+    "bundle-create",
+    "diagnose",
+    "check-encoding",
+    "has-commit",
+    "binary-files",
+    "abort",
+];
+
+/// Global options taking a value, skipped by the completions when looking for the subcommand
+const VALUE_OPTIONS: &[&str] = &[
+    "-c",
+    "--config",
+    "-p",
+    "--profile",
+    "--interval",
+    "--commit-time",
+    "--deepen",
+    "--sort-by",
+    "--tag",
+    "--format",
+    "--template",
+    "--columns",
+    "--color",
+    "-w",
+    "--workers",
+];
+
+/// Order of the repositories in the status table
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Name,
+    TreeSize,
+    Branch,
+    Distance,
+    Status,
+    Age,
+}
+
+impl FromStr for SortKey {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(SortKey::Name),
+            "tree-size" => Ok(SortKey::TreeSize),
+            "branch" => Ok(SortKey::Branch),
+            "distance" => Ok(SortKey::Distance),
+            "status" => Ok(SortKey::Status),
+            "age" => Ok(SortKey::Age),
+            _ => Err("expected 'name', 'tree-size', 'branch', 'distance', 'status' or 'age'"),
+        }
+    }
+}
+
+/// Format of the output of the subcommands describing a single repository
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ReportFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err("expected 'text' or 'json'"),
+        }
+    }
+}
+
+/// Format of the status of the repositories
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+    Tsv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            _ => Err("expected 'table', 'json', 'csv' or 'tsv'"),
+        }
+    }
+}
+
+/// Column of the status table
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Name,
+    Status,
+    Branch,
+    Distance,
+    Remote,
+    Age,
+    Stashes,
+    TreeSize,
+    Ignored,
+    Summary,
+}
+
+impl Column {
+    /// Name of the column as given to `--columns`
+    fn name(&self) -> &'static str {
+        match self {
+            Column::Name => "name",
+            Column::Status => "status",
+            Column::Branch => "branch",
+            Column::Distance => "distance",
+            Column::Remote => "remote",
+            Column::Age => "age",
+            Column::Stashes => "stashes",
+            Column::TreeSize => "tree-size",
+            Column::Ignored => "ignored",
+            Column::Summary => "summary",
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(Column::Name),
+            "status" => Ok(Column::Status),
+            "branch" => Ok(Column::Branch),
+            "distance" => Ok(Column::Distance),
+            "remote" => Ok(Column::Remote),
+            "age" => Ok(Column::Age),
+            "stashes" => Ok(Column::Stashes),
+            "tree-size" => Ok(Column::TreeSize),
+            "ignored" => Ok(Column::Ignored),
+            "summary" => Ok(Column::Summary),
+            _ => Err(format!("unknown column '{}'", s)),
+        }
+    }
+}
+
+/// When to color the status table
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err("expected 'auto', 'always' or 'never'"),
+        }
+    }
+}
+
+/// Message sent by the jobs processing repositories on the thread pool
+enum ProcessEvent {
+    /// The repository was fetched, with the errors which occurred
+    Fetched(Repository, Vec<anyhow::Error>),
+    /// The status of the repository was computed
+    StatusComputed(Repository, Result<()>),
+}
+
+/// What `pull` and `sync` did to a repository
+enum PullOutcome {
+    FastForwarded(usize),
+    /// The fast-forward failed with this message
+    Failed(String),
+    LocalChanges,
+    Diverged,
+    Ahead,
+    UpToDate,
+    NoUpstream,
+}
+
+/// Line printed for each repository, with `{field}` replaced by its value and `{{`, `}}`
+/// standing for literal braces
+struct Template(Vec<TemplatePart>);
+
+enum TemplatePart {
+    Literal(String),
+    Field(TemplateField),
+}
+
+enum TemplateField {
+    Name,
+    Path,
+    Branch,
+    Status,
+    Distance,
+    Remote,
+    Age,
+    Summary,
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => return Err(format!("unclosed field '{{{}'", field)),
+                        }
+                    }
+                    let field = match field.as_str() {
+                        "name" => TemplateField::Name,
+                        "path" => TemplateField::Path,
+                        "branch" => TemplateField::Branch,
+                        "status" => TemplateField::Status,
+                        "distance" => TemplateField::Distance,
+                        "remote" => TemplateField::Remote,
+                        "age" => TemplateField::Age,
+                        "summary" => TemplateField::Summary,
+                        _ => return Err(format!("unknown field '{}'", field)),
+                    };
+                    if !literal.is_empty() {
+                        parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(TemplatePart::Field(field));
+                }
+                '}' => return Err("unmatched '}', use '}}' for a literal brace".to_string()),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(literal));
+        }
+        Ok(Template(parts))
+    }
+}
+
 #[derive(StructOpt)]
 enum Command {
     /// Add new repositories
     Add {
+        /// Add paths even if they are not git repositories
+        #[structopt(short, long)]
+        force: bool,
+        /// How to name the repositories: last-component, last-two-components, remote-slug or custom:<NAME>
+        #[structopt(value_name = "STRATEGY", long, default_value = "last-component")]
+        name_strategy: NamingStrategy,
+        /// Tag the added repositories
+        #[structopt(value_name = "TAG", long = "tag", number_of_values = 1)]
+        tags: Vec<String>,
+        /// Always fetch the added repositories with their history truncated to N commits
+        #[structopt(value_name = "N", long)]
+        shallow: Option<u32>,
+        /// Add the repositories found in the directory trees of the paths, except those already
+        /// added
+        #[structopt(short, long)]
+        recursive: bool,
+        /// Maximum depth at which repositories are searched with --recursive
+        #[structopt(value_name = "N", long, default_value = "5")]
+        max_depth: usize,
         /// Paths to the repositories to add
         #[structopt(value_name = "PATH")]
         path: Vec<PathBuf>,
     },
+    /// List the repositories found in directory trees which are not added yet, with the names
+    /// they would be added under
+    Find {
+        /// How to name the repositories: last-component, last-two-components, remote-slug or custom:<NAME>
+        #[structopt(value_name = "STRATEGY", long, default_value = "last-component")]
+        name_strategy: NamingStrategy,
+        /// Maximum depth at which repositories are searched
+        #[structopt(value_name = "N", long, default_value = "5")]
+        max_depth: usize,
+        /// Roots of the directory trees to search
+        #[structopt(value_name = "PATH", required = true)]
+        path: Vec<PathBuf>,
+    },
+    /// Clone a repository and add it
+    Clone {
+        /// Directory to clone into, the repository being cloned in a subdirectory named after it
+        #[structopt(value_name = "DIR", long, default_value = ".")]
+        directory: PathBuf,
+        /// Name of the repository, the last component of its path by default
+        #[structopt(value_name = "NAME", long)]
+        name: Option<String>,
+        /// URL of the repository
+        #[structopt(value_name = "URL")]
+        url: String,
+    },
+    /// Add the repositories of another multi-repository manager
+    Import {
+        /// Manager the file comes from: gita (repos.csv), myrepos (.mrconfig), ghq (output of
+        /// `ghq list`), repo (manifest) or glim (manifest written by `glim export`)
+        #[structopt(value_name = "MANAGER", long)]
+        from: ImportFormat,
+        /// Replace repositories with the same name without asking
+        #[structopt(short, long)]
+        force: bool,
+        /// Directory the paths of a glim manifest are relative to, the home directory by default
+        #[structopt(value_name = "DIR", long)]
+        root: Option<PathBuf>,
+        /// File listing the repositories
+        #[structopt(value_name = "FILE")]
+        file: PathBuf,
+    },
+    /// Write the names, paths and URLs of the repositories to a manifest which can be imported
+    Export {
+        /// Directory the paths are made relative to, the home directory by default
+        #[structopt(value_name = "DIR", long)]
+        root: Option<PathBuf>,
+        /// File to write the manifest to
+        #[structopt(value_name = "FILE")]
+        file: PathBuf,
+    },
+    /// Write the current branch and commit of every repository to a manifest, as JSON if the file
+    /// has a `.json` extension
+    Freeze {
+        /// Directory the paths are made relative to, the home directory by default
+        #[structopt(value_name = "DIR", long)]
+        root: Option<PathBuf>,
+        /// File to write the manifest to
+        #[structopt(value_name = "FILE")]
+        file: PathBuf,
+    },
     /// Remove repositories
     Remove {
         /// Names of the repositories to remove
         #[structopt(value_name = "NAME")]
         name: Vec<String>,
     },
+    /// Remove repositories whose path no longer exists
+    Prune {
+        /// List the repositories which would be removed without removing them
+        #[structopt(long)]
+        dry_run: bool,
+    },
     /// Rename repository
     Rename {
         /// Current name of the repository
@@ -53,81 +610,2623 @@ enum Command {
         #[structopt(value_name = "NEW_NAME")]
         new_name: String,
     },
+    /// Show the branch, distance to the upstream, changed files, stashes and last commit of a
+    /// repository, without fetching it
+    Status {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Open a repository with the editor, the file manager or the browser
+    Open {
+        /// Open the directory with the file manager instead of `$VISUAL` or `$EDITOR`
+        #[structopt(long)]
+        files: bool,
+        /// Open the web page of the remote with the browser instead
+        #[structopt(long, conflicts_with = "files")]
+        web: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
     /// Get repository's path
     Path {
         /// Name of the repository
         #[structopt(value_name = "NAME")]
         name: String,
     },
-}
-
-impl CLI {
-    pub fn run(&mut self) -> Result<()> {
-        let ran_command = self.run_command()?;
-        // Process repositories and display only if a subcommand wasn't run
-        if !ran_command {
-            self.process_and_display()
-        } else {
-            Ok(())
-        }
-    }
-    fn run_command(&mut self) -> Result<bool> {
-        let mut ran_command = true;
-        let mut modified = false;
-        match &self.command {
-            Some(Command::Add { path }) => {
-                for path in path {
-                    self.config.add_repository(path)?;
-                    modified = true;
-                }
-            }
-            Some(Command::Remove { name }) => {
-                for name in name {
-                    if self.config.remove_repository_by_name(&name) {
-                        modified = true;
+    /// List the names and paths of the repositories, without opening them
+    List {
+        /// Only print the names, one per line
+        #[structopt(long)]
+        names: bool,
+    },
+    /// Print a completion script for a shell, which completes the names of the repositories
+    /// with bash, zsh and fish
+    Completions {
+        /// Shell to complete for
+        #[structopt(value_name = "SHELL", possible_values = &Shell::variants())]
+        shell: Shell,
+    },
+    /// Switch branch or create a new one
+    Checkout {
+        /// Create a new branch with this name
+        #[structopt(value_name = "NEW_BRANCH", short = "b")]
+        new_branch: Option<String>,
+        /// Overwrite the new branch if it already exists
+        #[structopt(short, long, requires = "new-branch")]
+        force: bool,
+        /// Switch every repository having this branch, locally or on a remote, to it
+        #[structopt(
+            value_name = "BRANCH",
+            long,
+            conflicts_with_all = &["new-branch", "name", "branch"]
+        )]
+        all: Option<String>,
+        /// Name of the repository
+        #[structopt(value_name = "NAME", required_unless = "all")]
+        name: Option<String>,
+        /// Branch to switch to, or start point of the new branch (HEAD by default)
+        #[structopt(value_name = "BRANCH", required_unless_one = &["new-branch", "all"])]
+        branch: Option<String>,
+    },
+    /// Fetch repositories without computing their status
+    Fetch {
+        /// Names of the repositories to fetch, all of them if none are given
+        name: Vec<String>,
+    },
+    /// Fast-forward clean repositories behind their upstream, never merging
+    Pull {
+        /// Names of the repositories to pull, all of them if none are given
+        name: Vec<String>,
+    },
+    /// Fetch repositories and fast-forward clean ones behind their upstream, showing what was done
+    /// to each in a table
+    Sync {
+        /// Names of the repositories to synchronize, all of them if none are given
+        name: Vec<String>,
+    },
+    /// Push the current branch of repositories
+    Push {
+        /// Show what would be pushed without pushing
+        #[structopt(long)]
+        dry_run: bool,
+        /// Names of the repositories to push, all those ahead of their upstream if none are given
+        #[structopt(value_name = "NAME")]
+        name: Vec<String>,
+    },
+    /// Run a shell command in every repository
+    Foreach {
+        /// Run the command in one repository at a time
+        #[structopt(long)]
+        serial: bool,
+        /// Only run the command in repositories whose name matches this glob
+        #[structopt(value_name = "PATTERN", long)]
+        filter: Option<glob::Pattern>,
+        /// Only show whether the command succeeded in each repository, in a table
+        #[structopt(long)]
+        table: bool,
+        /// Command to run, where {name} and {path} are replaced by those of the repository,
+        /// quoted for the shell, which are also set in $GLIM_NAME and $GLIM_PATH
+        #[structopt(value_name = "CMD")]
+        cmd: String,
+    },
+    /// Manage tags of a repository
+    Tag(TagCommand),
+    /// Manage the tags grouping repositories in the config
+    Group(GroupCommand),
+    /// Manage branches of a repository
+    Branch(BranchCommand),
+    /// Manage remotes of a repository
+    Remote(RemoteCommand),
+    /// Manage submodules of a repository
+    Submodule(SubmoduleCommand),
+    /// Manage reflogs of a repository
+    Reflog(ReflogCommand),
+    /// Manage notes of a repository
+    Notes(NotesCommand),
+    /// Manage stashes of a repository
+    Stash(StashCommand),
+    /// List the local branches of every repository with their upstream and distance to it
+    Branches {
+        /// Only list the branches without an upstream or ahead of it
+        #[structopt(long)]
+        unpushed: bool,
+    },
+    /// Show the recent commits of every repository, newest first
+    Log {
+        /// Maximum number of commits of each repository
+        #[structopt(value_name = "N", short = "n", long, default_value = "10")]
+        count: usize,
+        /// Only show the commits of the last N days
+        #[structopt(value_name = "N", long)]
+        days: Option<i64>,
+        /// Only show the commits of a range, where a missing side means HEAD, after a line
+        /// counting them for each repository
+        #[structopt(value_name = "FROM..TO")]
+        range: Option<String>,
+    },
+    /// Show the commits touching a file
+    FileLog {
+        /// Maximum number of commits to show
+        #[structopt(value_name = "N", short = "n", long, default_value = "10")]
+        count: usize,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Path of the file relative to the repository root
+        #[structopt(value_name = "PATH")]
+        path: String,
+    },
+    /// Sign commits with GPG, rewriting the history of the current branch
+    Sign {
+        /// Sign all unsigned commits among the last ones
+        #[structopt(long)]
+        all_unsigned: bool,
+        /// Number of last commits considered by --all-unsigned
+        #[structopt(value_name = "N", short = "n", long, default_value = "10")]
+        count: usize,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Commit to sign
+        #[structopt(value_name = "OID", required_unless = "all-unsigned")]
+        oid: Option<String>,
+    },
+    /// Show a health report of a repository
+    Health {
+        /// Include reference counts
+        #[structopt(long)]
+        refs: bool,
+        /// Count reachable objects if they are not cached, which can be slow
+        #[structopt(long)]
+        compute_objects: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Check the integrity of a repository
+    Fsck {
+        /// Check that references point to existing objects
+        #[structopt(long)]
+        refs: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Manage grafts redefining the parents of commits
+    Graft(GraftCommand),
+    /// Find the commit introducing a bug by binary search
+    Bisect(BisectCommand),
+    /// Count the commits of a range
+    CountRange {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Range of commits, where a missing side means HEAD
+        #[structopt(value_name = "FROM..TO")]
+        range: String,
+    },
+    /// Stage all changes, including untracked and deleted files
+    StageAll {
+        /// Only show the files which would be staged
+        #[structopt(long)]
+        dry_run: bool,
+        /// Do not ask for confirmation
+        #[structopt(short, long)]
+        yes: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Commit the staged changes
+    Commit {
+        /// Sign the commit with GPG
+        #[structopt(long)]
+        sign: bool,
+        /// Message of the commit
+        #[structopt(value_name = "MESSAGE", short, long)]
+        message: String,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Show the failures of hooks
+    HookLog {
+        /// Install hooks logging the failures of the existing ones first
+        #[structopt(long)]
+        install: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Fetch a tag from the upstream remote
+    FetchTag {
+        /// Fetch the tag even if it already exists locally
+        #[structopt(long)]
+        force: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Name of the tag
+        #[structopt(value_name = "TAG")]
+        tag: String,
+    },
+    /// Push the tags of a repository
+    PushTags {
+        /// Remote to push to instead of the push remote
+        #[structopt(long, value_name = "REMOTE")]
+        remote: Option<String>,
+        /// Only push the tags missing on the remote
+        #[structopt(long)]
+        new_only: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Set the email of a repository
+    FixEmail {
+        /// Email to set instead of the expected one
+        #[structopt(long, value_name = "EMAIL")]
+        email: Option<String>,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Find the tracked files of a repository whose path matches a pattern
+    FindFile {
+        /// Match the pattern as a glob instead of a substring
+        #[structopt(long)]
+        glob: bool,
+        /// Only print the number of matching files
+        #[structopt(long)]
+        count_only: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Pattern to match the paths against
+        #[structopt(value_name = "PATTERN")]
+        pattern: String,
+    },
+    /// Print a patch of the changes between two references
+    Patch {
+        /// Include the content of binary files
+        #[structopt(long)]
+        binary: bool,
+        /// Compress the patch with gzip
+        #[structopt(long)]
+        gzip: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Reference to diff from
+        #[structopt(value_name = "FROM")]
+        from: String,
+        /// Reference to diff to
+        #[structopt(value_name = "TO")]
+        to: String,
+    },
+    /// List the local branches merged into another branch
+    MergedBranches {
+        /// Branch the branches are merged into
+        #[structopt(value_name = "BRANCH", long, default_value = "HEAD")]
+        into: String,
+        /// Delete the merged branches, except main, master, develop and the current branch
+        #[structopt(long)]
+        delete: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Run `git gc` in every repository and show how much space it saved
+    Gc {
+        /// Optimize the repositories more at the expense of time
+        #[structopt(long)]
+        aggressive: bool,
+    },
+    /// Check that every repository can be opened and fetched, and suggest fixes
+    Doctor {
+        /// Seconds after which a remote is considered unreachable
+        #[structopt(value_name = "SECONDS", long, default_value = "10")]
+        timeout: u64,
+    },
+    /// Check which remotes of a repository can be connected to
+    Ping {
+        /// Seconds after which a remote is considered unreachable
+        #[structopt(value_name = "SECONDS", long, default_value = "10")]
+        timeout: u64,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// List the recent commits adding large files
+    LargeCommits {
+        /// Size in megabytes above which a commit is large
+        #[structopt(value_name = "N", long, default_value = "10")]
+        threshold_mb: u64,
+        /// Suggest tools removing large files from the history
+        #[structopt(long)]
+        fix: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// List the authors of the recent commits
+    RecentContributors {
+        /// Number of days considered
+        #[structopt(value_name = "N", long, default_value = "30")]
+        days: u32,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Fetch the head of a GitHub pull request
+    FetchPr {
+        /// Remote hosting the pull request
+        #[structopt(value_name = "REMOTE", long, default_value = "origin")]
+        remote: String,
+        /// Also switch to a local branch `pr/<N>` at the fetched head, fast-forwarding it if it
+        /// exists
+        #[structopt(long)]
+        checkout: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Number of the pull request
+        #[structopt(value_name = "N")]
+        number: u32,
+    },
+    /// Fetch a single branch from a remote without changing the checkout
+    FetchBranch {
+        /// Remote to fetch the branch from
+        #[structopt(value_name = "REMOTE", long, default_value = "origin")]
+        remote: String,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Name of the branch on the remote
+        #[structopt(value_name = "BRANCH")]
+        branch: String,
+    },
+    /// Remove a key from the config of a repository
+    ConfigUnset {
+        /// Remove all the values of a multi-valued key
+        #[structopt(long)]
+        all: bool,
+        /// Allow removing user.name and user.email
+        #[structopt(short, long)]
+        force: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Key to remove, e.g. core.editor
+        #[structopt(value_name = "KEY")]
+        key: String,
+    },
+    /// Restore files to their content at HEAD
+    Restore {
+        /// Restore files even if they have unstaged changes
+        #[structopt(short, long)]
+        force: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Paths of the files relative to the repository root
+        #[structopt(value_name = "PATH", required = true)]
+        paths: Vec<String>,
+    },
+    /// Show the number of objects reachable from the references of a repository
+    ObjectCounts {
+        /// Count the objects if they are not cached, which can be slow
+        #[structopt(long)]
+        compute_objects: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// List the text files of the index with CRLF line endings
+    CheckEol {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Push a local branch
+    PushBranch {
+        /// Remote to push to
+        #[structopt(value_name = "REMOTE", long, default_value = "origin")]
+        remote: String,
+        /// Make the branch track the pushed branch
+        #[structopt(short = "u", long)]
+        set_upstream: bool,
+        /// Overwrite the remote branch even if its history diverged
+        #[structopt(short, long)]
+        force: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Name of the branch
+        #[structopt(value_name = "BRANCH")]
+        branch: String,
+    },
+    /// Fast-forward a branch of a fork to the one of its upstream repository
+    SyncFork {
+        /// Remote of the upstream repository
+        #[structopt(value_name = "REMOTE", long, default_value = "upstream")]
+        upstream: String,
+        /// Branch to synchronize
+        #[structopt(value_name = "BRANCH", long, default_value = "main")]
+        branch: String,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Unstage changes of files
+    Unstage {
+        /// Unstage all changes
+        #[structopt(long)]
+        all: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Paths of the files relative to the repository root
+        #[structopt(value_name = "PATH", required_unless = "all")]
+        paths: Vec<String>,
+    },
+    /// List the files excluded by the ignore rules
+    Ignored {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Count the lines inserted and deleted between two references
+    Stats {
+        /// Output format
+        #[structopt(
+            value_name = "FORMAT",
+            long,
+            default_value = "text",
+            possible_values = &["text", "json"]
+        )]
+        format: ReportFormat,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Reference to diff from
+        #[structopt(value_name = "FROM")]
+        from: String,
+        /// Reference to diff to
+        #[structopt(value_name = "TO")]
+        to: String,
+    },
+    /// List the references of a bundle
+    BundleInfo {
+        /// Path of the bundle
+        #[structopt(value_name = "PATH")]
+        path: PathBuf,
+    },
+    /// Create a bundle of a repository
+    BundleCreate {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Path of the bundle to create
+        #[structopt(value_name = "OUTPUT")]
+        output: PathBuf,
+        /// References to bundle instead of all of them
+        #[structopt(value_name = "REF")]
+        refs: Vec<String>,
+    },
+    /// Show a snapshot of the state of a repository
+    Diagnose {
+        /// Output format
+        #[structopt(
+            value_name = "FORMAT",
+            long,
+            default_value = "text",
+            possible_values = &["text", "json"]
+        )]
+        format: ReportFormat,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// List the commit messages which are not UTF-8 and the paths which are not ASCII
+    CheckEncoding {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Exit with a non-zero status if a commit is missing from a repository
+    HasCommit {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Full id of the commit
+        #[structopt(value_name = "OID")]
+        oid: git2::Oid,
+    },
+    /// List the binary files of the index
+    BinaryFiles {
+        /// Suggest a .gitattributes fragment moving them to Git LFS
+        #[structopt(long)]
+        suggest_lfs: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Show the status table in an interactive interface refreshed in the background
+    Tui {
+        /// Interval in seconds between refreshes
+        #[structopt(value_name = "SECONDS", long, default_value = "60")]
+        refresh: u64,
+        /// Number of recent commits in the detail pane
+        #[structopt(value_name = "N", long, default_value = "10")]
+        commits: usize,
+    },
+    /// Abort an in-progress merge, rebase or cherry-pick
+    Abort {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+}
+
+#[derive(StructOpt)]
+enum GraftCommand {
+    /// List grafts
+    List {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Redefine the parents of a commit
+    Add {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Commit whose parents are redefined
+        #[structopt(value_name = "COMMIT")]
+        child: String,
+        /// New parents of the commit, none making it a root commit
+        #[structopt(value_name = "PARENT")]
+        parents: Vec<String>,
+    },
+    /// Remove the graft of a commit
+    Remove {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Commit whose graft is removed
+        #[structopt(value_name = "COMMIT")]
+        child: String,
+    },
+}
+
+#[derive(StructOpt)]
+enum BisectCommand {
+    /// Start bisecting between a bad and a good commit
+    Start {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Commit where the bug is present
+        #[structopt(value_name = "BAD")]
+        bad: String,
+        /// Commit where the bug is absent
+        #[structopt(value_name = "GOOD")]
+        good: String,
+    },
+    /// Mark the current commit as good
+    Good {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Mark the current commit as bad
+    Bad {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Skip the current commit
+    Skip {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// End the bisection and go back to the original branch
+    Reset {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+}
+
+#[derive(StructOpt)]
+enum TagCommand {
+    /// Delete a tag
+    Delete {
+        /// Also delete the tag on this remote
+        #[structopt(value_name = "REMOTE", short, long)]
+        remote: Option<String>,
+        /// Do not ask for confirmation
+        #[structopt(short, long)]
+        yes: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Name of the tag to delete
+        #[structopt(value_name = "TAG")]
+        tag: String,
+    },
+}
+
+#[derive(StructOpt)]
+enum GroupCommand {
+    /// Tag repositories in the config, to group them with others
+    Add {
+        /// Tag to add, such as `work`
+        #[structopt(value_name = "TAG")]
+        tag: String,
+        /// Names of the repositories to tag
+        #[structopt(value_name = "NAME", required = true)]
+        name: Vec<String>,
+    },
+    /// Remove a tag from repositories in the config
+    Remove {
+        /// Tag to remove
+        #[structopt(value_name = "TAG")]
+        tag: String,
+        /// Names of the repositories to untag
+        #[structopt(value_name = "NAME", required = true)]
+        name: Vec<String>,
+    },
+    /// List the tags of the config and their repositories
+    List {
+        /// Only list the repositories with this tag
+        #[structopt(value_name = "TAG")]
+        tag: Option<String>,
+    },
+}
+
+#[derive(StructOpt)]
+enum NotesCommand {
+    /// List notes attached to commits
+    List {
+        /// Only show the note of this commit
+        #[structopt(value_name = "COMMIT", long)]
+        annotated: Option<String>,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+    /// Remove the note of a commit
+    Remove {
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Commit whose note is removed
+        #[structopt(value_name = "COMMIT")]
+        commit: String,
+    },
+}
+
+#[derive(StructOpt)]
+enum StashCommand {
+    /// List the stashes of every repository with their age
+    List,
+    /// Show the changes of a stash without applying it
+    Show {
+        /// Index of the stash, as in stash@{N}
+        #[structopt(value_name = "N", long, default_value = "0")]
+        index: usize,
+        /// Print the full diff instead of its statistics
+        #[structopt(short, long)]
+        patch: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+}
+
+#[derive(StructOpt)]
+enum ReflogCommand {
+    /// Irreversibly delete entries of a reflog
+    Purge {
+        /// Reference whose reflog is purged
+        #[structopt(value_name = "REF", long = "ref", default_value = "HEAD")]
+        reference: String,
+        /// Delete all entries
+        #[structopt(long)]
+        all: bool,
+        /// Index of an entry to delete, as in REF@{N}
+        #[structopt(value_name = "N", long, required_unless = "all")]
+        entry: Vec<usize>,
+        /// Do not ask for confirmation
+        #[structopt(short, long)]
+        yes: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+    },
+}
+
+#[derive(StructOpt)]
+enum SubmoduleCommand {
+    /// Update submodules to the commits recorded in the repository
+    Update {
+        /// Also update nested submodules
+        #[structopt(short, long)]
+        recursive: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Name of the submodule to update instead of all of them
+        #[structopt(value_name = "SUBMODULE")]
+        submodule: Option<String>,
+    },
+}
+
+#[derive(StructOpt)]
+enum RemoteCommand {
+    /// Set the URL a remote pushes to
+    SetPushUrl {
+        /// Check that the URL can be pushed to first
+        #[structopt(long)]
+        verify: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Name of the remote
+        #[structopt(value_name = "REMOTE")]
+        remote: String,
+        /// New push URL
+        #[structopt(value_name = "URL")]
+        url: String,
+    },
+}
+
+#[derive(StructOpt)]
+enum BranchCommand {
+    /// Rename a local branch
+    Rename {
+        /// Overwrite the branch if it already exists
+        #[structopt(short, long)]
+        force: bool,
+        /// Name of the repository
+        #[structopt(value_name = "NAME")]
+        name: String,
+        /// Current name of the branch
+        #[structopt(value_name = "OLD_NAME")]
+        old_name: String,
+        /// New name of the branch
+        #[structopt(value_name = "NEW_NAME")]
+        new_name: String,
+    },
+}
+
+impl Cli {
+    pub fn run(&mut self) -> Result<()> {
+        if let Some(profile) = &self.profile {
+            let overlay = self.config.profile(profile)?;
+            let config = std::mem::take(&mut self.config);
+            self.config = config.with_overlay(overlay);
+        }
+        if self.prune_unreachable {
+            let removed = self.config.prune_unreachable_paths();
+            for name in &removed {
+                println!("Removed '{}' (path unreachable)", name);
+            }
+            if !removed.is_empty() {
+                self.config.save()?;
+            }
+        }
+        let ran_command = self.run_command()?;
+        // Process repositories and display only if a subcommand wasn't run
+        if !ran_command {
+            self.process_and_display()
+        } else {
+            Ok(())
+        }
+    }
+    fn run_command(&mut self) -> Result<bool> {
+        let mut ran_command = true;
+        let mut modified = false;
+        match &self.command {
+            Some(Command::Add {
+                force,
+                name_strategy,
+                tags,
+                shallow,
+                recursive,
+                max_depth,
+                path,
+            }) => {
+                let paths = if *recursive {
+                    self.find_new_repositories(path, *max_depth)?
+                } else {
+                    path.clone()
+                };
+                for path in &paths {
+                    if !force {
+                        check_is_repository(path)?;
+                    }
+                    let name = detect_name(path, name_strategy)?;
+                    self.config.add_repository(&name, path)?;
+                    for tag in tags {
+                        self.config.add_tag(&name, tag)?;
+                    }
+                    if let Some(depth) = shallow {
+                        self.config.set_shallow(&name, *depth)?;
+                    }
+                    if *recursive {
+                        println!("Added '{}' ({})", name, path.display());
+                    }
+                    modified = true;
+                }
+            }
+            Some(Command::Find {
+                name_strategy,
+                max_depth,
+                path,
+            }) => {
+                let mut table = new_table();
+                for path in self.find_new_repositories(path, *max_depth)? {
+                    let name = detect_name(&path, name_strategy)?;
+                    table.add_row(row![name, path.display()]);
+                }
+                self.print_table(&table);
+            }
+            Some(Command::Clone {
+                directory,
+                name,
+                url,
+            }) => {
+                let path = directory.join(clone_directory_name(url)?);
+                let name = match name {
+                    Some(name) => name.clone(),
+                    None => NamingStrategy::LastComponent.name_from_path(&path)?,
+                };
+                if self.config.repositories().contains_key(&name) {
+                    return Err(anyhow!("name '{}' already exists", name));
+                }
+                Repository::clone_with_name(&name, url, &path)
+                    .with_context(|| format!("failed to clone '{}'", url))?;
+                self.config.add_repository(&name, path.canonicalize()?)?;
+                println!("Cloned '{}' into {}", name, path.display());
+                modified = true;
+            }
+            Some(Command::Import {
+                from,
+                force,
+                root,
+                file,
+            }) => {
+                for imported in import::read_repositories(*from, file, root.as_deref())? {
+                    let ImportedRepository {
+                        name, path, url, ..
+                    } = imported;
+                    if let Some(existing) = self.config.repositories().get(&name) {
+                        if existing.path == path {
+                            continue;
+                        }
+                        let prompt = format!(
+                            "Replace '{}' ({}) with {}?",
+                            name,
+                            existing.path.display(),
+                            path.display()
+                        );
+                        if !*force && !confirm(&prompt)? {
+                            continue;
+                        }
+                        self.config.remove_repository_by_name(&name);
+                    }
+                    // Repositories of a glim manifest may not have been cloned on this machine
+                    match &url {
+                        Some(url) if !path.exists() => {
+                            Repository::clone_with_name(&name, url, &path)
+                                .with_context(|| format!("failed to clone '{}'", url))?;
+                            println!("Cloned '{}' into {}", name, path.display());
+                        }
+                        _ => {}
+                    }
+                    self.config.add_repository(&name, &path)?;
+                    println!("Added '{}' ({})", name, path.display());
+                    modified = true;
+                }
+            }
+            Some(Command::Export { root, file }) => {
+                let repositories = self
+                    .config
+                    .to_pairs()
+                    .into_iter()
+                    .map(|(name, path)| ImportedRepository {
+                        name: name.to_string(),
+                        path: path.canonicalize().unwrap_or_else(|_| path.to_owned()),
+                        url: Repository::open_with_name(name, path)
+                            .ok()
+                            .and_then(|repository| repository.fetch_url()),
+                        branch: None,
+                        commit: None,
+                    })
+                    .collect::<Vec<_>>();
+                let count = repositories.len();
+                import::write_manifest(file, root.as_deref(), repositories)?;
+                println!("Exported {} repositories to {}", count, file.display());
+            }
+            Some(Command::Freeze { root, file }) => {
+                let mut report = ErrorReport::default();
+                let mut repositories = Vec::new();
+                for repository in self.open_repositories(&mut report) {
+                    let name = repository.name();
+                    let (branch, commit) = match repository.head_commit() {
+                        Ok(head) => head,
+                        Err(e) => {
+                            report.add(name, &e.context("failed to resolve HEAD"));
+                            continue;
+                        }
+                    };
+                    let path = &self.config.repositories()[name].path;
+                    repositories.push(ImportedRepository {
+                        name: name.to_string(),
+                        path: path.canonicalize().unwrap_or_else(|_| path.to_owned()),
+                        url: repository.fetch_url(),
+                        branch,
+                        commit: Some(commit.to_string()),
+                    });
+                }
+                if !report.is_empty() {
+                    eprint!("{}", report);
+                    return Err(anyhow!("{} repositories could not be frozen", report.len()));
+                }
+                let count = repositories.len();
+                import::write_manifest(file, root.as_deref(), repositories)?;
+                println!("Froze {} repositories to {}", count, file.display());
+            }
+            Some(Command::Remove { name }) => {
+                for name in name {
+                    if self.config.remove_repository_by_name(name) {
+                        modified = true;
+                    }
+                }
+            }
+            Some(Command::Prune { dry_run }) => {
+                let names = self.config.unreachable_paths();
+                for name in &names {
+                    let path = &self.config.repositories()[name].path;
+                    if *dry_run {
+                        println!("Would remove '{}' ({})", name, path.display());
+                    } else {
+                        println!("Removed '{}' ({})", name, path.display());
+                    }
+                }
+                if !*dry_run && !names.is_empty() {
+                    self.config.prune_unreachable_paths();
+                    modified = true;
+                }
+            }
+            Some(Command::Rename { name, new_name }) => {
+                self.config.rename_repository(name, new_name)?;
+                modified = true;
+            }
+            Some(Command::Path { name }) => {
+                let repository = self
+                    .config
+                    .repositories()
+                    .get(name)
+                    .context("name does not exist")?;
+                println!("{:?}", repository.path);
+            }
+            Some(Command::Status { name }) => {
+                self.print_repository_status(name)?;
+            }
+            Some(Command::Open { files, web, name }) => {
+                let path = &self
+                    .config
+                    .repositories()
+                    .get(name)
+                    .context("name does not exist")?
+                    .path;
+                if *web {
+                    let url = self.open_repository(name)?.web_url()?;
+                    open_with_system(&url)?;
+                    println!("Opened {}", url);
+                } else if *files {
+                    open_with_system(path)?;
+                } else {
+                    open_in_editor(path)?;
+                }
+            }
+            Some(Command::List { names: true }) => {
+                for name in self.config.to_name_list() {
+                    println!("{}", name);
+                }
+            }
+            Some(Command::List { names: false }) => {
+                let mut table = new_table();
+                for (name, path) in self.config.to_pairs() {
+                    table.add_row(row![name, path.display()]);
+                }
+                table.printstd();
+            }
+            Some(Command::Completions { shell }) => {
+                print_completions(*shell);
+            }
+            Some(Command::Checkout {
+                new_branch,
+                force,
+                all,
+                name,
+                branch,
+            }) => {
+                if let Some(branch) = all {
+                    self.checkout_all(branch)?;
+                } else {
+                    // The name is required without --all
+                    let repository = self.open_repository(name.as_deref().unwrap_or_default())?;
+                    match (new_branch, branch) {
+                        (Some(new_branch), start_point) => {
+                            let start_point = start_point.as_deref().unwrap_or("HEAD");
+                            repository.checkout_create_branch(new_branch, start_point, *force)?;
+                            println!("Switched to a new branch '{}'", new_branch);
+                        }
+                        (None, Some(branch)) => {
+                            repository.checkout_branch(branch)?;
+                            println!("Switched to branch '{}'", branch);
+                        }
+                        (None, None) => unreachable!(),
+                    }
+                }
+            }
+            Some(Command::Fetch { name }) => {
+                self.fetch(name)?;
+            }
+            Some(Command::Pull { name }) => {
+                self.pull(name)?;
+            }
+            Some(Command::Sync { name }) => {
+                self.sync(name)?;
+            }
+            Some(Command::Push { dry_run, name }) => {
+                self.push(name, *dry_run)?;
+            }
+            Some(Command::Foreach {
+                serial,
+                filter,
+                table,
+                cmd,
+            }) => {
+                self.foreach(cmd, filter.as_ref(), *serial, *table)?;
+            }
+            Some(Command::Tag(TagCommand::Delete {
+                remote,
+                yes,
+                name,
+                tag,
+            })) => {
+                let repository = self.open_repository(name)?;
+                let (target, message) = repository.tag_target(tag)?;
+                println!("{} -> {}", tag, target);
+                if let Some(message) = message {
+                    println!("{}", message.trim_end());
+                }
+                if *yes || confirm(&format!("Delete tag '{}'?", tag))? {
+                    repository.tag_delete(tag)?;
+                    if let Some(remote) = remote {
+                        repository
+                            .push_tag_deletion(remote, tag)
+                            .with_context(|| format!("failed to delete tag on '{}'", remote))?;
+                    }
+                }
+            }
+            Some(Command::Group(GroupCommand::Add { tag, name })) => {
+                for name in name {
+                    if self.config.add_tag(name, tag)? {
+                        modified = true;
+                    }
+                }
+            }
+            Some(Command::Group(GroupCommand::Remove { tag, name })) => {
+                for name in name {
+                    if self.config.remove_tag(name, tag)? {
+                        modified = true;
+                    }
+                }
+            }
+            Some(Command::Group(GroupCommand::List { tag: filter })) => {
+                let mut tags = BTreeMap::<&String, Vec<&str>>::new();
+                for (name, repository) in self.config.repositories() {
+                    for tag in &repository.tags {
+                        tags.entry(tag).or_default().push(name);
+                    }
+                }
+                let mut table = new_table();
+                for (tag, mut names) in tags {
+                    if filter.as_ref().is_some_and(|filter| filter != tag) {
+                        continue;
+                    }
+                    names.sort_unstable();
+                    table.add_row(row![tag, names.join(", ")]);
+                }
+                table.printstd();
+            }
+            Some(Command::Branches { unpushed }) => {
+                let mut report = ErrorReport::default();
+                let mut table = new_table();
+                table.set_titles(row!["NAME", "BRANCH", "UPSTREAM", "DISTANCE"]);
+                for repository in self.open_repositories(&mut report) {
+                    let branches = match repository.branches() {
+                        Ok(branches) => branches,
+                        Err(e) => {
+                            report.add(repository.name(), &e);
+                            continue;
+                        }
+                    };
+                    for branch in branches {
+                        let is_unpushed = branch.distance.as_ref().is_none_or(|d| d.ahead() > 0);
+                        if *unpushed && !is_unpushed {
+                            continue;
+                        }
+                        let marker = if branch.is_head { "* " } else { "  " };
+                        table.add_row(row![
+                            repository.name(),
+                            format!("{}{}", marker, branch.name),
+                            branch.upstream.unwrap_or_default(),
+                            branch
+                                .distance
+                                .map(|distance| self.format_distance(&distance))
+                                .unwrap_or_default()
+                        ]);
+                    }
+                }
+                if !table.is_empty() {
+                    table.printstd();
+                }
+                if !report.is_empty() {
+                    eprint!("{}", report);
+                }
+            }
+            Some(Command::Log { count, days, range }) => {
+                let range = range.as_deref().map(split_range).transpose()?;
+                let mut report = ErrorReport::default();
+                let mut commits = Vec::new();
+                for repository in self.open_repositories(&mut report) {
+                    let result = match range {
+                        Some((from, to)) => {
+                            repository.rev_count_range(from, to).and_then(|total| {
+                                let branch = match to {
+                                    "HEAD" => repository.branch_name(),
+                                    _ => None,
+                                };
+                                let branch = branch.as_deref().unwrap_or(to);
+                                println!("{}: {} commits in {}", repository.name(), total, branch);
+                                repository.range_commits(from, to, *count)
+                            })
+                        }
+                        None => repository.recent_commits(*count),
+                    };
+                    match result {
+                        Ok(recent_commits) => commits.extend(
+                            recent_commits
+                                .into_iter()
+                                .map(|commit| (repository.name().to_string(), commit)),
+                        ),
+                        Err(e) => report.add(repository.name(), &e),
+                    }
+                }
+                if let Some(days) = days {
+                    let since = Utc::now() - chrono::Duration::days(*days);
+                    commits.retain(|(_, commit)| commit.time >= since);
+                }
+                commits.sort_by_key(|(_, commit)| Reverse(commit.time));
+                let mut table = new_table();
+                for (name, commit) in commits {
+                    table.add_row(row![
+                        commit.time.format("%Y-%m-%d %H:%M"),
+                        name,
+                        commit.short_id(),
+                        commit.author,
+                        commit.summary
+                    ]);
+                }
+                table.printstd();
+                if !report.is_empty() {
+                    eprint!("{}", report);
+                }
+            }
+            Some(Command::Stash(StashCommand::List)) => {
+                let mut report = ErrorReport::default();
+                let mut table = new_table();
+                table.set_titles(row!["NAME", "STASH", "AGE", "MESSAGE"]);
+                let mut count = 0;
+                for repository in self.open_repositories(&mut report) {
+                    let stashes = match repository.stash_list() {
+                        Ok(stashes) => stashes,
+                        Err(e) => {
+                            report.add(repository.name(), &e);
+                            continue;
+                        }
+                    };
+                    for (i, stash) in stashes.iter().enumerate() {
+                        table.add_row(row![
+                            repository.name(),
+                            format!("stash@{{{}}}", i),
+                            format_age(Utc::now().signed_duration_since(stash.time)),
+                            stash.summary
+                        ]);
+                        count += 1;
+                    }
+                }
+                if count > 0 {
+                    table.printstd();
+                }
+                println!("{} stashes", count);
+                if !report.is_empty() {
+                    eprint!("{}", report);
+                }
+            }
+            Some(Command::FileLog { count, name, path }) => {
+                let repository = self.open_repository(name)?;
+                let mailmap = repository.read_mailmap();
+                let mut table = new_table();
+                for commit in repository.file_history(path, *count)? {
+                    let (author, _) =
+                        repository::normalize_author(&mailmap, &commit.author, &commit.email);
+                    table.add_row(row![
+                        commit.short_id(),
+                        commit.time.format("%Y-%m-%d %H:%M"),
+                        author,
+                        commit.summary
+                    ]);
+                }
+                table.printstd();
+            }
+            Some(Command::Sign {
+                all_unsigned,
+                count,
+                name,
+                oid,
+            }) => {
+                let repository = self.open_repository(name)?;
+                if *all_unsigned {
+                    let oids = repository.unsigned_commits(*count)?;
+                    if oids.is_empty() {
+                        println!("No unsigned commits");
+                    } else {
+                        let head = repository.sign_commits(&oids)?;
+                        println!("Signed {} commits, HEAD is now {}", oids.len(), head);
+                    }
+                } else if let Some(oid) = oid {
+                    let oid = repository.resolve_commit(oid)?;
+                    repository.sign_commit(oid)?;
+                    println!("Signed {}", oid);
+                }
+            }
+            Some(Command::Health {
+                refs,
+                compute_objects,
+                name,
+            }) => {
+                let repository = self.open_repository(name)?;
+                println!("state: {}", repository::state_name(repository.state()));
+                let object_counts = if *compute_objects {
+                    Some(repository.count_reachable_objects()?)
+                } else {
+                    repository.cached_object_counts()
+                };
+                if let Some(object_counts) = object_counts {
+                    println!("objects: {}", object_counts);
+                }
+                if *refs {
+                    let total = repository.ref_count()?;
+                    let packed = repository.packed_ref_count();
+                    let loose = total.saturating_sub(packed);
+                    println!("refs: {} ({} packed, {} loose)", total, packed, loose);
+                    if loose > 100 {
+                        println!("hint: many loose refs, run `git pack-refs --all` to pack them");
+                    }
+                }
+            }
+            Some(Command::Fsck { refs, name }) => {
+                let repository = self.open_repository(name)?;
+                // Run every check if none is selected
+                let all = !*refs;
+                if *refs || all {
+                    let broken_refs = repository.unreachable_refs();
+                    for reference in &broken_refs {
+                        println!("broken ref: {}", reference);
+                    }
+                    if !broken_refs.is_empty() {
+                        return Err(anyhow!("{} broken references", broken_refs.len()));
+                    }
+                }
+            }
+            Some(Command::Graft(GraftCommand::List { name })) => {
+                let repository = self.open_repository(name)?;
+                for (child, parents) in repository.grafts() {
+                    let parents = parents.iter().map(git2::Oid::to_string).collect::<Vec<_>>();
+                    println!("{} {}", child, parents.join(" "));
+                }
+            }
+            Some(Command::Graft(GraftCommand::Add {
+                name,
+                child,
+                parents,
+            })) => {
+                let repository = self.open_repository(name)?;
+                let child = repository.resolve_commit(child)?;
+                let parents = parents
+                    .iter()
+                    .map(|parent| repository.resolve_commit(parent))
+                    .collect::<Result<Vec<_>>>()?;
+                repository.add_graft(child, &parents)?;
+            }
+            Some(Command::Graft(GraftCommand::Remove { name, child })) => {
+                let repository = self.open_repository(name)?;
+                let child = repository.resolve_commit(child)?;
+                repository.remove_graft(child)?;
+            }
+            Some(Command::Bisect(command)) => {
+                let step = match command {
+                    BisectCommand::Start { name, bad, good } => {
+                        let repository = self.open_repository(name)?;
+                        let bad = repository.resolve_commit(bad)?;
+                        let good = repository.resolve_commit(good)?;
+                        Some(repository.bisect_start(good, bad)?)
+                    }
+                    BisectCommand::Good { name } => {
+                        Some(self.open_repository(name)?.bisect_good()?)
+                    }
+                    BisectCommand::Bad { name } => Some(self.open_repository(name)?.bisect_bad()?),
+                    BisectCommand::Skip { name } => {
+                        Some(self.open_repository(name)?.bisect_skip()?)
+                    }
+                    BisectCommand::Reset { name } => {
+                        self.open_repository(name)?.bisect_reset()?;
+                        None
+                    }
+                };
+                match step {
+                    Some(BisectStep::Test(oid, left)) => {
+                        println!("Bisecting: {} commits left to test, now at {}", left, oid)
+                    }
+                    Some(BisectStep::Found(oid)) => println!("{} is the first bad commit", oid),
+                    None => {}
+                }
+            }
+            Some(Command::CountRange { name, range }) => {
+                let repository = self.open_repository(name)?;
+                let (from, to) = split_range(range)?;
+                println!("{}", repository.rev_count_range(from, to)?);
+            }
+            Some(Command::StageAll { dry_run, yes, name }) => {
+                let repository = self.open_repository(name)?;
+                let paths = repository.unstaged_paths()?;
+                for path in paths.iter() {
+                    println!("{}", path);
+                }
+                if paths.is_empty() {
+                    println!("Nothing to stage");
+                } else if !dry_run && (*yes || confirm(&format!("Stage {} files?", paths.len()))?) {
+                    let count = repository.index_add_all()?;
+                    println!("Staged {} files", count);
+                }
+            }
+            Some(Command::Commit {
+                sign,
+                message,
+                name,
+            }) => {
+                let repository = self.open_repository(name)?;
+                let oid = if *sign {
+                    repository.commit_with_gpg(message)?
+                } else {
+                    // A repository without commits has an unborn HEAD
+                    let parents = repository
+                        .resolve_commit("HEAD")
+                        .into_iter()
+                        .collect::<Vec<_>>();
+                    repository.create_commit(message, &parents)?
+                };
+                let summary = message.lines().next().unwrap_or_default();
+                println!("{} {}", &oid.to_string()[..7], summary);
+            }
+            Some(Command::HookLog { install, name }) => {
+                let repository = self.open_repository(name)?;
+                if *install {
+                    for hook in repository.install_logging_hooks()? {
+                        println!("Logging failures of '{}'", hook);
+                    }
+                }
+                let mut table = new_table();
+                for failure in repository.detect_hook_failures() {
+                    let time = Utc
+                        .timestamp_opt(failure.timestamp as i64, 0)
+                        .single()
+                        .map(|time| {
+                            time.with_timezone(&Local)
+                                .format("%Y-%m-%d %H:%M")
+                                .to_string()
+                        })
+                        .unwrap_or_default();
+                    table.add_row(row![
+                        time,
+                        failure.hook,
+                        failure.exit_code,
+                        failure.stderr.lines().next().unwrap_or_default()
+                    ]);
+                }
+                table.printstd();
+            }
+            Some(Command::FetchTag { force, name, tag }) => {
+                let repository = self.open_repository(name)?;
+                repository
+                    .fetch_tag(tag, *force)
+                    .with_context(|| format!("failed to fetch tag '{}'", tag))?;
+                println!("{} -> {}", tag, repository.tag_target(tag)?.0);
+            }
+            Some(Command::PushTags {
+                remote,
+                new_only,
+                name,
+            }) => {
+                let repository = self.open_repository(name)?;
+                let remote = match remote {
+                    Some(remote) => remote.clone(),
+                    None => self.push_remote(name, &repository)?,
+                };
+                let tags = repository
+                    .push_all_tags(&remote, *new_only)
+                    .with_context(|| format!("failed to push tags to '{}'", remote))?;
+                for tag in &tags {
+                    println!("{}", tag);
+                }
+                println!("Pushed {} tags to '{}'", tags.len(), remote);
+            }
+            Some(Command::FixEmail { email, name }) => {
+                let repository = self.open_repository(name)?;
+                let email = email
+                    .as_ref()
+                    .or_else(|| self.expected_email(name))
+                    .ok_or_else(|| anyhow!("'{}' has no expected email", name))?;
+                repository.set_email(email)?;
+                println!("Set email of '{}' to '{}'", name, email);
+            }
+            Some(Command::FindFile {
+                glob,
+                count_only,
+                name,
+                pattern,
+            }) => {
+                let repository = self.open_repository(name)?;
+                let paths = repository.list_tracked_paths_matching(pattern, *glob)?;
+                if *count_only {
+                    println!("{}", paths.len());
+                } else {
+                    for path in paths {
+                        println!("{}", path);
+                    }
+                }
+            }
+            Some(Command::Branch(BranchCommand::Rename {
+                force,
+                name,
+                old_name,
+                new_name,
+            })) => {
+                let repository = self.open_repository(name)?;
+                repository.branch_rename(old_name, new_name, *force)?;
+                println!("Renamed '{}' to '{}'", old_name, new_name);
+            }
+            Some(Command::Patch {
+                binary,
+                gzip,
+                name,
+                from,
+                to,
+            }) => {
+                let repository = self.open_repository(name)?;
+                let patch = repository.create_patch(from, to, *binary)?;
+                if *gzip {
+                    let mut child = process::Command::new("gzip")
+                        .arg("-c")
+                        .stdin(process::Stdio::piped())
+                        .spawn()
+                        .context("failed to run gzip")?;
+                    child.stdin.take().unwrap().write_all(&patch)?;
+                    if !child.wait()?.success() {
+                        return Err(anyhow!("gzip failed"));
+                    }
+                } else {
+                    io::stdout().write_all(&patch)?;
+                }
+            }
+            Some(Command::Remote(RemoteCommand::SetPushUrl {
+                verify,
+                name,
+                remote,
+                url,
+            })) => {
+                let repository = self.open_repository(name)?;
+                let old_url = repository.push_url(remote)?;
+                println!("old push URL: {}", old_url.as_deref().unwrap_or("none"));
+                if *verify {
+                    repository
+                        .verify_push_url(url)
+                        .with_context(|| format!("'{}' is not reachable", url))?;
+                }
+                repository.set_push_url(remote, url)?;
+                println!("new push URL: {}", url);
+            }
+            Some(Command::Submodule(SubmoduleCommand::Update {
+                recursive,
+                name,
+                submodule,
+            })) => {
+                let repository = self.open_repository(name)?;
+                let results = match submodule {
+                    Some(submodule) => vec![(
+                        submodule.clone(),
+                        repository.submodule_update(submodule, *recursive),
+                    )],
+                    None => repository.update_all_submodules(*recursive),
+                };
+                let mut failed = 0;
+                for (submodule, result) in results {
+                    match result {
+                        Ok(()) => println!("{}: updated", submodule),
+                        Err(e) => {
+                            println!("{}: {:#}", submodule, e);
+                            failed += 1;
+                        }
+                    }
+                }
+                if failed > 0 {
+                    return Err(anyhow!("failed to update {} submodules", failed));
+                }
+            }
+            Some(Command::MergedBranches { into, delete, name }) => {
+                let repository = self.open_repository(name)?;
+                let current_branch = repository.branch_name();
+                for branch in repository.list_merged_branches(into) {
+                    let protected = PROTECTED_BRANCHES.contains(&branch.as_str())
+                        || current_branch.as_ref() == Some(&branch);
+                    if *delete && !protected {
+                        repository.branch_delete(&branch)?;
+                        println!("{} (deleted)", branch);
+                    } else {
+                        println!("{}", branch);
+                    }
+                }
+            }
+            Some(Command::Gc { aggressive }) => {
+                self.gc(*aggressive)?;
+            }
+            Some(Command::Doctor { timeout }) => {
+                self.doctor(Duration::from_secs(*timeout))?;
+            }
+            Some(Command::Ping { timeout, name }) => {
+                let repository = self.open_repository(name)?;
+                let mut table = new_table();
+                for (remote, reachable) in
+                    repository.remotes_with_connectivity(Duration::from_secs(*timeout))
+                {
+                    table.add_row(row![
+                        remote,
+                        if reachable {
+                            "reachable"
+                        } else {
+                            "unreachable"
+                        }
+                    ]);
+                }
+                table.printstd();
+            }
+            Some(Command::LargeCommits {
+                threshold_mb,
+                fix,
+                name,
+            }) => {
+                let repository = self.open_repository(name)?;
+                let large_commits = repository.detect_large_commits(threshold_mb * MEGABYTE);
+                let mut table = new_table();
+                for (oid, size) in &large_commits {
+                    table.add_row(row![
+                        &oid.to_string()[..7],
+                        format!("{:.1} MB", *size as f64 / MEGABYTE as f64),
+                        repository.commit_summary_of(*oid).unwrap_or_default()
+                    ]);
+                }
+                table.printstd();
+                if *fix && !large_commits.is_empty() {
+                    println!("hint: remove large files from the history with `git filter-repo --strip-blobs-bigger-than {}M`", threshold_mb);
+                    println!(
+                        "hint: or with BFG Repo-Cleaner: `bfg --strip-blobs-bigger-than {}M`",
+                        threshold_mb
+                    );
+                }
+            }
+            Some(Command::RecentContributors { days, name }) => {
+                let repository = self.open_repository(name)?;
+                let mut table = new_table();
+                for (email, count) in repository.list_contributors_since(*days) {
+                    table.add_row(row![email, count]);
+                }
+                table.printstd();
+            }
+            Some(Command::FetchPr {
+                remote,
+                checkout,
+                name,
+                number,
+            }) => {
+                let repository = self.open_repository(name)?;
+                repository
+                    .fetch_pull_request(*number, remote)
+                    .with_context(|| format!("failed to fetch pull request #{}", number))?;
+                let remote_branch = format!("{}/pr/{}", remote, number);
+                println!("Fetched #{} to '{}'", number, remote_branch);
+                if *checkout {
+                    let branch = format!("pr/{}", number);
+                    let count = repository.checkout_fast_forward(&branch, &remote_branch)?;
+                    if count > 0 {
+                        println!("Fast-forwarded '{}' by {} commits", branch, count);
+                    }
+                    println!("Switched to '{}'", branch);
+                }
+            }
+            Some(Command::FetchBranch {
+                remote,
+                name,
+                branch,
+            }) => {
+                let repository = self.open_repository(name)?;
+                repository
+                    .fetch_specific_branch(branch, remote)
+                    .with_context(|| format!("failed to fetch branch '{}'", branch))?;
+                let remote_branch = format!("{}/{}", remote, branch);
+                let oid = repository.resolve_commit(&remote_branch)?;
+                println!("Fetched '{}' at {}", remote_branch, oid);
+            }
+            Some(Command::ConfigUnset {
+                all,
+                force,
+                name,
+                key,
+            }) => {
+                let repository = self.open_repository(name)?;
+                // Keys are case-insensitive except for subsections
+                let lowercase_key = key.to_lowercase();
+                if !force && ["user.name", "user.email"].contains(&lowercase_key.as_str()) {
+                    return Err(anyhow!(
+                        "'{}' sets the identity of commits, use --force to unset it",
+                        key
+                    ));
+                }
+                repository.config_unset(key, *all)?;
+                println!("Unset '{}' in '{}'", key, name);
+                // Hooks, remotes and upstreams are used by other subcommands
+                if lowercase_key == "core.hookspath"
+                    || lowercase_key.starts_with("remote.")
+                    || lowercase_key.starts_with("branch.")
+                {
+                    eprintln!(
+                        "warning: unsetting '{}' may change the behavior of glim",
+                        key
+                    );
+                }
+            }
+            Some(Command::Restore { force, name, paths }) => {
+                let repository = self.open_repository(name)?;
+                let old_ids = paths
+                    .iter()
+                    .map(|path| repository.workdir_blob_id(path))
+                    .collect::<Vec<_>>();
+                if !force {
+                    let unstaged_paths = repository.unstaged_paths()?;
+                    // Deleted files have no changes to lose
+                    let modified = paths
+                        .iter()
+                        .zip(&old_ids)
+                        .filter(|(path, old_id)| old_id.is_some() && unstaged_paths.contains(path))
+                        .map(|(path, _)| path.as_str())
+                        .collect::<Vec<_>>();
+                    if !modified.is_empty() {
+                        return Err(anyhow!(
+                            "unstaged changes would be lost, use --force to restore: {}",
+                            modified.join(", ")
+                        ));
+                    }
+                }
+                repository.index_checkout(&paths.iter().map(String::as_str).collect::<Vec<_>>())?;
+                let short_id = |oid: Option<git2::Oid>| {
+                    oid.map_or_else(
+                        || "missing".to_string(),
+                        |oid| oid.to_string()[..7].to_string(),
+                    )
+                };
+                for (path, old_id) in paths.iter().zip(old_ids) {
+                    let new_id = repository.workdir_blob_id(path);
+                    println!("{}: {} -> {}", path, short_id(old_id), short_id(new_id));
+                }
+            }
+            Some(Command::ObjectCounts {
+                compute_objects,
+                name,
+            }) => {
+                let repository = self.open_repository(name)?;
+                let object_counts = if *compute_objects {
+                    repository.count_reachable_objects()?
+                } else {
+                    repository.cached_object_counts().ok_or_else(|| {
+                        anyhow!(
+                            "objects of '{}' are not counted yet, use --compute-objects",
+                            name
+                        )
+                    })?
+                };
+                println!("{}", object_counts);
+            }
+            Some(Command::Reflog(ReflogCommand::Purge {
+                reference,
+                all,
+                entry,
+                yes,
+                name,
+            })) => {
+                let repository = self.open_repository(name)?;
+                let entries = if *all { None } else { Some(entry.as_slice()) };
+                let prompt = match entries {
+                    Some(entries) => format!(
+                        "Irreversibly delete {} entries of the reflog of '{}'?",
+                        entries.len(),
+                        reference
+                    ),
+                    None => format!("Irreversibly purge the whole reflog of '{}'?", reference),
+                };
+                if *yes || confirm(&prompt)? {
+                    let count = repository.ref_log_delete(reference, entries)?;
+                    println!("Purged {} entries of the reflog of '{}'", count, reference);
+                }
+            }
+            Some(Command::Notes(NotesCommand::List { annotated, name })) => {
+                let repository = self.open_repository(name)?;
+                let annotated = annotated
+                    .as_ref()
+                    .map(|commit| repository.resolve_commit(commit))
+                    .transpose()?;
+                for note in repository.notes_list()? {
+                    if annotated.is_some_and(|oid| oid != note.annotated_oid) {
+                        continue;
+                    }
+                    println!("{} {}", note.annotated_oid, note.note_oid);
+                    for line in note.content.lines() {
+                        println!("    {}", line);
+                    }
+                }
+            }
+            Some(Command::Notes(NotesCommand::Remove { name, commit })) => {
+                let repository = self.open_repository(name)?;
+                let oid = repository.resolve_commit(commit)?;
+                repository.note_remove(oid)?;
+                println!("Removed the note of {}", oid);
+            }
+            Some(Command::Stash(StashCommand::Show { index, patch, name })) => {
+                let repository = self.open_repository(name)?;
+                if *patch {
+                    io::stdout().write_all(&repository.stash_patch(*index)?)?;
+                } else {
+                    println!("{}", repository.stash_show(*index)?);
+                }
+            }
+            Some(Command::CheckEol { name }) => {
+                let repository = self.open_repository(name)?;
+                let paths = repository.detect_non_lf_line_endings();
+                for path in &paths {
+                    println!("{}", path);
+                }
+                if !paths.is_empty() {
+                    println!("hint: add `* text=auto eol=lf` to .gitattributes and run `git add --renormalize .`");
+                }
+            }
+            Some(Command::PushBranch {
+                remote,
+                set_upstream,
+                force,
+                name,
+                branch,
+            }) => {
+                let repository = self.open_repository(name)?;
+                repository
+                    .push_branch(branch, remote, *set_upstream, *force)
+                    .with_context(|| format!("failed to push '{}'", branch))?;
+                println!("Pushed '{}' to '{}'", branch, remote);
+            }
+            Some(Command::SyncFork {
+                upstream,
+                branch,
+                name,
+            }) => {
+                let repository = self.open_repository(name)?;
+                let result = repository
+                    .sync_fork(upstream, branch)
+                    .with_context(|| format!("failed to sync '{}'", branch))?;
+                if !result.fetched {
+                    println!("No new commits on '{}/{}'", upstream, branch);
+                }
+                if result.merged_commits > 0 {
+                    println!(
+                        "Fast-forwarded '{}' by {} commits from '{}'",
+                        branch, result.merged_commits, upstream
+                    );
+                } else if result.conflicts {
+                    return Err(anyhow!(
+                        "'{}' diverged from '{}/{}' and merging would conflict",
+                        branch,
+                        upstream,
+                        branch
+                    ));
+                } else if result.ahead > 0 && result.behind > 0 {
+                    println!(
+                        "'{}' diverged from '{}/{}', merge or rebase it",
+                        branch, upstream, branch
+                    );
+                } else if result.ahead > 0 {
+                    println!(
+                        "'{}' is {} commits ahead of '{}/{}'",
+                        branch, result.ahead, upstream, branch
+                    );
+                } else {
+                    println!("'{}' is up to date", branch);
+                }
+            }
+            Some(Command::Unstage { all, name, paths }) => {
+                let repository = self.open_repository(name)?;
+                let staged_paths = repository.staged_paths()?;
+                let paths = if *all {
+                    staged_paths
+                } else {
+                    let not_staged = paths
+                        .iter()
+                        .filter(|path| !staged_paths.contains(path))
+                        .map(String::as_str)
+                        .collect::<Vec<_>>();
+                    if !not_staged.is_empty() {
+                        return Err(anyhow!("not staged: {}", not_staged.join(", ")));
+                    }
+                    paths.clone()
+                };
+                if !paths.is_empty() {
+                    repository
+                        .index_remove(&paths.iter().map(String::as_str).collect::<Vec<_>>())?;
+                }
+                for path in paths {
+                    println!("Unstaged '{}'", path);
+                }
+            }
+            Some(Command::Ignored { name }) => {
+                let repository = self.open_repository(name)?;
+                for path in repository.list_ignored_files()? {
+                    println!("{}", path);
+                }
+            }
+            Some(Command::Stats {
+                format,
+                name,
+                from,
+                to,
+            }) => {
+                let repository = self.open_repository(name)?;
+                let (insertions, deletions) = repository.total_additions_deletions(from, to)?;
+                if *format == ReportFormat::Json {
+                    let stats = serde_json::json!({
+                        "from": from,
+                        "to": to,
+                        "insertions": insertions,
+                        "deletions": deletions,
+                    });
+                    println!("{}", stats);
+                } else {
+                    println!("{} insertions(+), {} deletions(-)", insertions, deletions);
+                }
+            }
+            Some(Command::BundleInfo { path }) => {
+                for (oid, name) in Repository::open_bundle(path)? {
+                    println!("{} {}", oid, name);
+                }
+            }
+            Some(Command::BundleCreate { name, output, refs }) => {
+                let repository = self.open_repository(name)?;
+                repository
+                    .create_bundle(output, &refs.iter().map(String::as_str).collect::<Vec<_>>())?;
+                println!("Created '{}'", output.display());
+            }
+            Some(Command::Diagnose { format, name }) => {
+                let repository = self.open_repository(name)?;
+                let report = repository.diagnose();
+                if *format == ReportFormat::Json {
+                    let diagnostics = serde_json::json!({
+                        "name": name,
+                        "diagnostics": report,
+                    });
+                    println!("{}", diagnostics);
+                } else {
+                    println!("{}", report);
+                }
+            }
+            Some(Command::CheckEncoding { name }) => {
+                let repository = self.open_repository(name)?;
+                for (_, issue) in repository.detect_encoding_issues() {
+                    println!("{}", issue);
+                }
+            }
+            Some(Command::HasCommit { name, oid }) => {
+                let repository = self.open_repository(name)?;
+                if !repository.commit_exists(*oid) {
+                    process::exit(1);
+                }
+            }
+            Some(Command::BinaryFiles { suggest_lfs, name }) => {
+                let repository = self.open_repository(name)?;
+                let paths = repository.detect_binary_files_in_index();
+                for path in &paths {
+                    println!("{}", path);
+                }
+                if *suggest_lfs && !paths.is_empty() {
+                    // Track files by extension when they have one
+                    let patterns = paths
+                        .iter()
+                        .map(|path| match Path::new(path).extension() {
+                            Some(extension) => format!("*.{}", extension.to_string_lossy()),
+                            None => path.replace(' ', "[[:space:]]"),
+                        })
+                        .collect::<BTreeSet<_>>();
+                    println!();
+                    println!("# .gitattributes");
+                    for pattern in patterns {
+                        println!("{} filter=lfs diff=lfs merge=lfs -text", pattern);
+                    }
+                }
+            }
+            Some(Command::Tui { refresh, commits }) => {
+                self.run_tui(Duration::from_secs(*refresh), *commits)?;
+            }
+            Some(Command::Abort { name }) => {
+                use git2::RepositoryState::*;
+                let repository = self.open_repository(name)?;
+                let state = repository.state();
+                match state {
+                    Merge => repository.merge_abort()?,
+                    Rebase | RebaseInteractive | RebaseMerge => repository.rebase_abort()?,
+                    CherryPick | CherryPickSequence => repository.cherry_pick_abort()?,
+                    Clean => return Err(anyhow!("nothing to abort")),
+                    _ => return Err(anyhow!("cannot abort {}", repository::state_name(state))),
+                }
+                println!("Aborted {}", repository::state_name(state));
+            }
+            None => {
+                ran_command = false;
+            }
+        }
+
+        // Save config
+        if modified {
+            self.config.save().context("failed to save config")?;
+        }
+        Ok(ran_command)
+    }
+    fn workers(&self) -> usize {
+        self.workers.or_else(|| self.config.workers()).unwrap_or(4)
+    }
+    /// Run a shell command in the directory of every repository and print outputs as they complete
+    fn foreach(
+        &self,
+        cmd: &str,
+        filter: Option<&glob::Pattern>,
+        serial: bool,
+        table: bool,
+    ) -> Result<()> {
+        let pool = ThreadPool::new(if serial { 1 } else { self.workers() });
+        let (tx, rx) = channel();
+        let mut num_jobs = 0;
+
+        for (name, path) in self.config.to_pairs() {
+            if filter.is_some_and(|filter| !filter.matches(name)) {
+                continue;
+            }
+            num_jobs += 1;
+            let tx = tx.clone();
+            let name = name.to_string();
+            let path = path.to_owned();
+            let cmd = cmd
+                .replace("{name}", &shell_quote(&name))
+                .replace("{path}", &shell_quote(&path.to_string_lossy()));
+
+            pool.execute(move || {
+                let output = shell_command(&cmd)
+                    .current_dir(&path)
+                    .env("GLIM_NAME", &name)
+                    .env("GLIM_PATH", &path)
+                    .output();
+                tx.send((name, output)).unwrap();
+            });
+        }
+
+        if table {
+            return self.print_foreach_table(rx.iter().take(num_jobs).collect());
+        }
+        let mut failed = Vec::new();
+        for (name, output) in rx.iter().take(num_jobs) {
+            match output {
+                Ok(output) => {
+                    match output.status.code() {
+                        Some(code) => println!("==> {} (exit {})", name, code),
+                        None => println!("==> {} (killed)", name),
+                    }
+                    io::stdout().write_all(&output.stdout)?;
+                    io::stdout().write_all(&output.stderr)?;
+                    if !output.status.success() {
+                        failed.push(name);
+                    }
+                }
+                Err(e) => {
+                    println!("==> {} (failed to run: {})", name, e);
+                    failed.push(name);
+                }
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            failed.sort();
+            Err(anyhow!("command failed in: {}", failed.join(", ")))
+        }
+    }
+    /// Fetch the named repositories, or all of them if `names` is empty, on the thread pool
+    fn fetch(&self, names: &[String]) -> Result<()> {
+        let mut report = ErrorReport::default();
+        let repositories = self.open_named_repositories(names, &mut report);
+
+        let pool = ThreadPool::new(self.workers());
+        let (tx, rx) = channel();
+        let num_jobs = repositories.len();
+        for repository in repositories {
+            let tx = tx.clone();
+            pool.execute(move || {
+                let result = repository.fetch();
+                tx.send((repository, result)).unwrap();
+            });
+        }
+
+        let mut fetched = 0;
+        for (repository, result) in rx.iter().take(num_jobs) {
+            match result {
+                Ok(()) => fetched += 1,
+                Err(e) => report.add(repository.name(), &e.context("failed to fetch")),
+            }
+        }
+        println!("Fetched {} repositories", fetched);
+        if report.is_empty() {
+            Ok(())
+        } else {
+            eprint!("{}", report);
+            Err(anyhow!(
+                "{} repositories could not be fetched",
+                report.len()
+            ))
+        }
+    }
+    /// Fetch the named repositories, or all of them if `names` is empty, and fast-forward those
+    /// which are clean and strictly behind their upstream, returning what was done to each
+    fn fast_forward_repositories(
+        &self,
+        names: &[String],
+        report: &mut ErrorReport,
+    ) -> Vec<(String, Option<Distance>, PullOutcome)> {
+        let repositories = self.open_named_repositories(names, report);
+        let sorted_map = self.process(repositories, report, |_| {});
+
+        let mut outcomes = Vec::with_capacity(sorted_map.len());
+        for (name, repository) in sorted_map {
+            let is_dirty = repository
+                .status()
+                .is_some_and(|status| !status.to_string().is_empty());
+            let distance = repository.distance();
+            let outcome = match distance {
+                Some(Distance::Behind(_)) if is_dirty => PullOutcome::LocalChanges,
+                Some(Distance::Behind(_)) => match repository.fast_forward() {
+                    Ok(count) => PullOutcome::FastForwarded(count),
+                    Err(e) => {
+                        let message = format!("{:#}", e);
+                        report.add(&name, &e.context("failed to fast-forward"));
+                        PullOutcome::Failed(message)
+                    }
+                },
+                Some(Distance::Both(_, _)) => PullOutcome::Diverged,
+                Some(Distance::Ahead(_)) => PullOutcome::Ahead,
+                Some(Distance::Same) => PullOutcome::UpToDate,
+                None => PullOutcome::NoUpstream,
+            };
+            outcomes.push((name, distance, outcome));
+        }
+        outcomes
+    }
+    /// Fetch the named repositories, or all of them if `names` is empty, and fast-forward those
+    /// which are clean and behind their upstream
+    fn pull(&self, names: &[String]) -> Result<()> {
+        let mut report = ErrorReport::default();
+        let mut updated = 0;
+        let mut skipped = 0;
+        for (name, _, outcome) in self.fast_forward_repositories(names, &mut report) {
+            match outcome {
+                PullOutcome::FastForwarded(count) => {
+                    println!("{}: fast-forwarded {} commits", name, count);
+                    updated += 1;
+                }
+                PullOutcome::LocalChanges => {
+                    println!("{}: skipped, local changes", name);
+                    skipped += 1;
+                }
+                PullOutcome::Diverged => {
+                    println!("{}: skipped, diverged from upstream", name);
+                    skipped += 1;
+                }
+                _ => {}
+            }
+        }
+        println!("Updated {} repositories, skipped {}", updated, skipped);
+        if report.is_empty() {
+            Ok(())
+        } else {
+            eprint!("{}", report);
+            Err(anyhow!("{} repositories could not be pulled", report.len()))
+        }
+    }
+    /// Fetch the named repositories, or all of them if `names` is empty, fast-forward those which
+    /// are clean and strictly behind their upstream, and show the result of each in a table
+    fn sync(&self, names: &[String]) -> Result<()> {
+        let mut report = ErrorReport::default();
+        let mut table = new_table();
+        table.set_titles(row!["NAME", "DISTANCE", "RESULT"]);
+        let (mut updated, mut skipped) = (0, 0);
+        for (name, distance, outcome) in self.fast_forward_repositories(names, &mut report) {
+            let distance = distance
+                .map(|distance| self.format_distance(&distance))
+                .unwrap_or_default();
+            match outcome {
+                PullOutcome::FastForwarded(count) => {
+                    let result = format!("fast-forwarded {} commits", count);
+                    table.add_row(row![name, distance, Fg->result]);
+                    updated += 1;
+                }
+                PullOutcome::Failed(message) => {
+                    table.add_row(row![name, distance, Fr->message]);
+                }
+                PullOutcome::LocalChanges => {
+                    table.add_row(row![name, distance, Fy->"skipped, local changes"]);
+                    skipped += 1;
+                }
+                PullOutcome::Diverged => {
+                    table.add_row(row![name, distance, Fy->"skipped, diverged from upstream"]);
+                    skipped += 1;
+                }
+                PullOutcome::Ahead => {
+                    table.add_row(row![name, distance, "ahead of upstream"]);
+                }
+                PullOutcome::UpToDate => {
+                    table.add_row(row![name, distance, "up to date"]);
+                }
+                PullOutcome::NoUpstream => {
+                    table.add_row(row![name, distance, "no upstream"]);
+                }
+            }
+        }
+        if !table.is_empty() {
+            self.print_table(&table);
+        }
+        println!("Updated {} repositories, skipped {}", updated, skipped);
+        if report.is_empty() {
+            Ok(())
+        } else {
+            eprint!("{}", report);
+            Err(anyhow!(
+                "{} repositories could not be synchronized",
+                report.len()
+            ))
+        }
+    }
+    /// Switch every repository to a branch, creating it from a remote branch if needed, and show
+    /// the result of each in a table
+    fn checkout_all(&self, branch: &str) -> Result<()> {
+        let mut report = ErrorReport::default();
+        let repositories = self.open_repositories(&mut report);
+
+        let mut table = new_table();
+        table.set_titles(row!["NAME", "RESULT"]);
+        let (mut switched, mut created, mut skipped) = (0, 0, 0);
+        for repository in repositories {
+            let name = repository.name();
+            match repository.switch_branch(branch) {
+                Ok(BranchSwitch::Switched) => {
+                    table.add_row(row![name, Fg->"switched"]);
+                    switched += 1;
+                }
+                Ok(BranchSwitch::Created(upstream)) => {
+                    table.add_row(row![name, Fg->format!("created, tracking '{}'", upstream)]);
+                    created += 1;
+                }
+                Ok(BranchSwitch::Unchanged) => {
+                    table.add_row(row![name, "already on branch"]);
+                }
+                Ok(BranchSwitch::Missing) => {
+                    table.add_row(row![name, Fy->"skipped, no such branch"]);
+                    skipped += 1;
+                }
+                Err(e) => report.add(name, &e.context("failed to switch branch")),
+            }
+        }
+        if !table.is_empty() {
+            self.print_table(&table);
+        }
+        println!(
+            "Switched {} repositories, created {} branches, skipped {}",
+            switched, created, skipped
+        );
+        if report.is_empty() {
+            Ok(())
+        } else {
+            eprint!("{}", report);
+            Err(anyhow!(
+                "{} repositories could not be switched",
+                report.len()
+            ))
+        }
+    }
+    /// Push the current branch of the named repositories, or of all those ahead of their upstream
+    /// if `names` is empty, and show the result of each in a table
+    fn push(&self, names: &[String], dry_run: bool) -> Result<()> {
+        let mut report = ErrorReport::default();
+        let repositories = if names.is_empty() {
+            let repositories = self.open_repositories(&mut report);
+            self.process(repositories, &mut report, |_| {})
+                .into_values()
+                .filter(|repository| matches!(repository.distance(), Some(Distance::Ahead(_))))
+                .collect()
+        } else {
+            self.open_named_repositories(names, &mut report)
+        };
+
+        let mut table = new_table();
+        table.set_titles(row!["NAME", "REMOTE", "RESULT"]);
+        let mut failed = 0;
+        for repository in repositories {
+            let name = repository.name();
+            let remote = match self.push_remote(name, &repository) {
+                Ok(remote) => remote,
+                Err(e) => {
+                    table.add_row(row![name, "", Fr->format!("{:#}", e)]);
+                    failed += 1;
+                    continue;
+                }
+            };
+            let commits = match repository.distance() {
+                Some(distance) => format!(" {} commits", distance.ahead()),
+                None => String::new(),
+            };
+            if dry_run {
+                table.add_row(row![name, remote, format!("would push{}", commits)]);
+            } else {
+                match repository.push(&remote) {
+                    Ok(()) => table.add_row(row![name, remote, Fg->format!("pushed{}", commits)]),
+                    Err(e) => {
+                        failed += 1;
+                        table.add_row(row![name, remote, Fr->format!("{:#}", e)])
+                    }
+                };
+            }
+        }
+        if !table.is_empty() {
+            self.print_table(&table);
+        }
+        if !report.is_empty() {
+            eprint!("{}", report);
+        }
+        if failed == 0 && report.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{} repositories could not be pushed",
+                failed + report.len()
+            ))
+        }
+    }
+    /// Run `git gc` in every repository on the thread pool and show their sizes in a table
+    fn gc(&self, aggressive: bool) -> Result<()> {
+        let mut report = ErrorReport::default();
+        let repositories = self.open_repositories(&mut report);
+        let pool = ThreadPool::new(self.workers());
+        let (tx, rx) = channel();
+        let num_jobs = repositories.len();
+        for repository in repositories {
+            let tx = tx.clone();
+            pool.execute(move || {
+                let result = repository.gc(aggressive);
+                tx.send((repository.name().to_string(), result)).unwrap();
+            });
+        }
+
+        let mut results = rx.iter().take(num_jobs).collect::<Vec<_>>();
+        results.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut table = new_table();
+        table.set_titles(row!["NAME", "BEFORE", "AFTER", "SAVED"]);
+        let mut saved = 0;
+        for (name, result) in results {
+            match result {
+                Ok((before, after)) => {
+                    saved += before.saturating_sub(after);
+                    table.add_row(row![
+                        name,
+                        format_size(before),
+                        format_size(after),
+                        format_size(before.saturating_sub(after))
+                    ]);
+                }
+                Err(e) => report.add(&name, &e),
+            }
+        }
+        if !table.is_empty() {
+            table.printstd();
+        }
+        println!("Saved {}", format_size(saved));
+        if report.is_empty() {
+            Ok(())
+        } else {
+            eprint!("{}", report);
+            Err(anyhow!(
+                "{} repositories could not be garbage collected",
+                report.len()
+            ))
+        }
+    }
+    /// Check every repository on the thread pool and show their problems in a table
+    fn doctor(&self, timeout: Duration) -> Result<()> {
+        let pool = ThreadPool::new(self.workers());
+        let (tx, rx) = channel();
+        let mut num_jobs = 0;
+
+        for (name, path) in self.config.to_pairs() {
+            if !self.has_tags(name) {
+                continue;
+            }
+            let tx = tx.clone();
+            let name = name.to_string();
+            let path = path.to_owned();
+            num_jobs += 1;
+
+            pool.execute(move || {
+                if std::fs::metadata(&path).is_err() {
+                    tx.send((name, None, vec![repository::Problem::MissingPath]))
+                        .unwrap();
+                    return;
+                }
+                match Repository::open_with_name(&name, &path) {
+                    Ok(repository) => {
+                        let problems = repository.problems(timeout);
+                        tx.send((name, Some(repository), problems)).unwrap();
                     }
+                    Err(_) => tx
+                        .send((name, None, vec![repository::Problem::NotRepository]))
+                        .unwrap(),
+                }
+            });
+        }
+
+        let mut results = rx.iter().take(num_jobs).collect::<Vec<_>>();
+        results.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+        let mut table = new_table();
+        table.set_titles(row!["NAME", "PROBLEM", "SUGGESTION"]);
+        let mut failed = 0;
+        for (name, repository, mut problems) in results {
+            // A detached HEAD is already reported as such
+            if let (Some(repository), Some(expected_branch)) =
+                (&repository, self.expected_branch(&name))
+            {
+                if repository.branch_name().is_some()
+                    && self.is_on_unexpected_branch(&name, repository)
+                {
+                    problems.push(repository::Problem::UnexpectedBranch(
+                        expected_branch.clone(),
+                    ));
                 }
             }
-            Some(Command::Rename { name, new_name }) => {
-                self.config.rename_repository(&name, &new_name)?;
-                modified = true;
+            if problems.is_empty() {
+                table.add_row(row![name, Fg->"ok", ""]);
+                continue;
             }
-            Some(Command::Path { name }) => {
-                let path = self
-                    .config
-                    .repositories()
-                    .get(name)
-                    .context("name does not exist")?;
-                println!("{:?}", path);
+            failed += 1;
+            for problem in problems {
+                table.add_row(row![name, Fr->problem, problem.suggestion()]);
             }
-            None => {
-                ran_command = false;
+        }
+        if !table.is_empty() {
+            self.print_table(&table);
+        }
+
+        if failed == 0 {
+            Ok(())
+        } else {
+            Err(anyhow!("{} repositories have problems", failed))
+        }
+    }
+    /// Open the named repositories, or all of them if `names` is empty
+    fn open_named_repositories(
+        &self,
+        names: &[String],
+        report: &mut ErrorReport,
+    ) -> Vec<Repository> {
+        if names.is_empty() {
+            return self.open_repositories(report);
+        }
+        let mut repositories = Vec::with_capacity(names.len());
+        for name in names {
+            match self.open_repository(name) {
+                Ok(repository) => repositories.push(repository),
+                Err(e) => report.add(name, &e.context("failed to open")),
             }
         }
+        repositories
+    }
+    /// Run a program in the repositories whose name matches `filter` and show the exit codes in a
+    /// table
+    /// Show whether a command run by `foreach` succeeded in each repository in a table
+    fn print_foreach_table(
+        &self,
+        mut results: Vec<(String, io::Result<process::Output>)>,
+    ) -> Result<()> {
+        results.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut table = new_table();
+        table.set_titles(row!["NAME", "EXIT", "RESULT"]);
+        let mut failed = 0;
+        for (name, output) in results {
+            match output.map(|output| output.status) {
+                Ok(status) if status.success() => table.add_row(row![name, "0", Fg->"pass"]),
+                Ok(status) => {
+                    failed += 1;
+                    let code = status
+                        .code()
+                        .map_or_else(|| "killed".to_string(), |code| code.to_string());
+                    table.add_row(row![name, code, Fr->"fail"])
+                }
+                Err(e) => {
+                    failed += 1;
+                    table.add_row(row![name, "", Fr->format!("failed to run: {}", e)])
+                }
+            };
+        }
+        if !table.is_empty() {
+            self.print_table(&table);
+        }
 
-        // Save config
-        if modified {
-            self.config.save().context("failed to save config")?;
+        if failed == 0 {
+            Ok(())
+        } else {
+            Err(anyhow!("command failed in {} repositories", failed))
         }
-        Ok(ran_command)
+    }
+    /// Find the repositories in the directory trees of `roots` down to `max_depth`, except those
+    /// already added
+    fn find_new_repositories(&self, roots: &[PathBuf], max_depth: usize) -> Result<Vec<PathBuf>> {
+        let mut found = Vec::new();
+        for root in roots {
+            find_repositories(root, max_depth, &mut found)?;
+        }
+        let added = self
+            .config
+            .to_path_list()
+            .iter()
+            .filter_map(|path| path.canonicalize().ok())
+            .collect::<BTreeSet<_>>();
+        Ok(found
+            .into_iter()
+            .filter(|path| {
+                path.canonicalize()
+                    .map_or(true, |path| !added.contains(&path))
+            })
+            .collect())
+    }
+    fn open_repository(&self, name: &str) -> Result<Repository> {
+        let repository_config = self
+            .config
+            .repositories()
+            .get(name)
+            .context("name does not exist")?;
+        Repository::open_with_name(name, &repository_config.path)
     }
     fn process_and_display(&self) -> Result<()> {
+        let mut report = ErrorReport::default();
+        let mut repositories = self.open_repositories(&mut report);
+        let sorted_map = if self.stream {
+            let columns = self.columns();
+            // Names are marked when references are broken, so they are checked before sizing
+            for repository in &mut repositories {
+                let _ = repository.check_refs();
+            }
+            let mut widths = self.stream_widths(&columns, &repositories);
+            let sorted_map = self.process(repositories, &mut report, |repository| {
+                self.print_streamed_row(&columns, &mut widths, repository)
+            });
+            if !report.is_empty() {
+                eprint!("{}", report);
+            }
+            sorted_map
+        } else {
+            let sorted_map = self.process(repositories, &mut report, |_| {});
+            self.display(&sorted_map, &report);
+            sorted_map
+        };
+
+        if self.watch_paths {
+            return self.watch(sorted_map);
+        }
+
+        let unexpected_branches = sorted_map
+            .iter()
+            .filter(|(name, repository)| self.is_on_unexpected_branch(name, repository))
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        if self.check && !unexpected_branches.is_empty() {
+            Err(anyhow!(
+                "not on expected branch: {}",
+                unexpected_branches.join(", ")
+            ))
+        } else {
+            Ok(())
+        }
+    }
+    fn open_repositories(&self, report: &mut ErrorReport) -> Vec<Repository> {
         // Attempt to open repositories
         let mut repositories = Vec::with_capacity(self.config.repositories().len());
-        for (name, path) in self.config.repositories() {
-            match Repository::open(name, path) {
+        for (name, path) in self.config.to_pairs() {
+            if !self.has_tags(name) {
+                continue;
+            }
+            match Repository::open_with_name(name, path) {
                 Ok(repository) => repositories.push(repository),
-                Err(e) => eprintln!("Could not open '{}': {}", name, e),
+                Err(e) => report.add(name, &e.context("failed to open")),
             }
         }
-
+        repositories
+    }
+    fn process(
+        &self,
+        repositories: Vec<Repository>,
+        report: &mut ErrorReport,
+        on_processed: impl FnMut(&Repository),
+    ) -> BTreeMap<String, Repository> {
+        self.process_until(repositories, report, on_processed, &AtomicBool::new(false))
+    }
+    /// Process repositories until `cancelled` is set, returning those processed until then
+    fn process_until(
+        &self,
+        repositories: Vec<Repository>,
+        report: &mut ErrorReport,
+        mut on_processed: impl FnMut(&Repository),
+        cancelled: &AtomicBool,
+    ) -> BTreeMap<String, Repository> {
         // Create thread pool
-        let pool = ThreadPool::new(self.workers);
+        let pool = ThreadPool::new(self.workers());
         let (tx, rx) = channel();
         let num_jobs = repositories.len();
 
         // Create progress bar
-        let pb = ProgressBar::new(num_jobs as u64);
+        // Streamed rows show the progress instead, and it would garble the interactive interface
+        let draw_target = if self.stream || matches!(self.command, Some(Command::Tui { .. })) {
+            ProgressDrawTarget::hidden()
+        } else {
+            ProgressDrawTarget::stderr()
+        };
+        let pb = ProgressBar::with_draw_target(num_jobs as u64, draw_target);
         pb.set_style(
             ProgressStyle::default_bar()
                 .template("{prefix} [{bar:60}] {pos}/{len}: {msg}")
@@ -136,84 +3235,1171 @@ impl CLI {
         pb.set_prefix("Processing...");
 
         let do_fetch = !self.no_fetch;
+        let fetch_lfs = self.fetch_lfs;
+        let unshallow = self.unshallow;
 
-        // Process repositories on thread pool
+        // Fetch repositories on thread pool
         for repository in repositories.into_iter() {
-            let mut repository = repository;
             let tx = tx.clone();
             let pb = pb.clone();
+            let depth = self.deepen.or_else(|| self.shallow(repository.name()));
 
             pool.execute(move || {
+                let mut repository = repository;
+                let mut errors = Vec::new();
+
+                // Fetching could fail for another reason and hide broken references
+                let refs_ok = match repository.check_refs() {
+                    Ok(()) => true,
+                    Err(e) => {
+                        errors.push(e.context("not fetching"));
+                        false
+                    }
+                };
+
                 // Attempt to fetch from repository
-                if do_fetch {
-                    let _ = repository.fetch();
+                if do_fetch && refs_ok {
+                    let name = repository.name().to_string();
+                    let was_shallow = repository.is_shallow();
+                    let result = if unshallow || depth.is_some() {
+                        let result = repository.fetch_with_depth(depth.unwrap_or(0), unshallow);
+                        if was_shallow != repository.is_shallow() {
+                            pb.println(if was_shallow {
+                                format!("{}: [shallow] -> complete", name)
+                            } else {
+                                format!("{}: complete -> [shallow]", name)
+                            });
+                        }
+                        result
+                    } else {
+                        let pb = pb.clone();
+                        repository.fetch_with_progress(move |received, total| {
+                            pb.set_message(&format!("{} ({}/{})", name, received, total));
+                        })
+                    };
+                    match result {
+                        Ok(()) if fetch_lfs && repository.lfs_enabled() => {
+                            let remote = repository
+                                .upstream_remote()
+                                .unwrap_or_else(|| "origin".to_string());
+                            if let Err(e) = repository.fetch_lfs_objects(&remote) {
+                                errors.push(e.context("failed to fetch LFS objects"));
+                            }
+                        }
+                        Ok(()) => {}
+                        Err(e) => errors.push(e.context("failed to fetch")),
+                    }
                 }
-                // Compute status now since it can be slow
-                let _ = repository.compute_status();
 
-                // Update progress bar
-                pb.set_message(repository.name());
-                pb.inc(1);
-
-                tx.send(repository).unwrap();
+                // The receiver is gone if processing was cancelled
+                let _ = tx.send(ProcessEvent::Fetched(repository, errors));
             });
         }
 
-        // Join threads and collect data in a sorted map
-        let sorted_map = rx
-            .iter()
-            .take(num_jobs)
-            .fold(BTreeMap::new(), |mut map, repository| {
-                map.insert(repository.name().to_string(), repository);
-                map
-            });
+        // Compute status on the thread pool as soon as a repository is fetched since it can be slow,
+        // and collect data in a sorted map as soon as it is computed
+        let mut sorted_map = BTreeMap::new();
+        let mut remaining = num_jobs;
+        while remaining > 0 && !cancelled.load(Ordering::Relaxed) {
+            let event = match rx.recv_timeout(CANCEL_POLL_INTERVAL) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => unreachable!(),
+            };
+            match event {
+                ProcessEvent::Fetched(repository, errors) => {
+                    for error in errors {
+                        report.add(repository.name(), &error);
+                    }
+                    repository.compute_status_background(
+                        &pool,
+                        tx.clone(),
+                        ProcessEvent::StatusComputed,
+                    );
+                }
+                ProcessEvent::StatusComputed(repository, result) => {
+                    if let Err(e) = result {
+                        report.add(repository.name(), &e.context("failed to compute status"));
+                    }
+                    on_processed(&repository);
+
+                    // Update progress bar
+                    pb.set_message(repository.name());
+                    pb.inc(1);
+
+                    sorted_map.insert(repository.name().to_string(), repository);
+                    remaining -= 1;
+                }
+            }
+        }
 
         // Clear progress bar
         pb.finish_and_clear();
 
+        sorted_map
+    }
+    fn display(&self, sorted_map: &BTreeMap<String, Repository>, report: &ErrorReport) {
         // Create table
-        let mut table = Table::new();
+        let mut table = new_table();
+
+        // Sizes are only computed when needed since it reads every tree
+        let columns = self.columns();
+        let tree_sizes = if columns.contains(&Column::TreeSize) || self.sort_by == SortKey::TreeSize
+        {
+            sorted_map
+                .iter()
+                .map(|(name, repository)| (name, repository.tree_size("HEAD").unwrap_or(0)))
+                .collect::<HashMap<_, _>>()
+        } else {
+            HashMap::new()
+        };
+        let mut repositories = sorted_map.iter().collect::<Vec<_>>();
+        repositories.retain(|(_, repository)| self.is_shown(repository));
+        // Sorting is stable so repositories with the same key stay sorted by name
+        match self.sort_by {
+            SortKey::Name => {}
+            SortKey::TreeSize => repositories.sort_by_key(|(name, _)| Reverse(tree_sizes[name])),
+            SortKey::Branch => {
+                repositories.sort_by_cached_key(|(_, repository)| repository.branch_name())
+            }
+            SortKey::Distance => {
+                repositories.sort_by_cached_key(|(_, repository)| match repository.distance() {
+                    Some(Distance::Behind(_)) => 0,
+                    Some(Distance::Both(_, _)) => 1,
+                    Some(Distance::Ahead(_)) => 2,
+                    Some(Distance::Same) => 3,
+                    None => 4,
+                })
+            }
+            SortKey::Status => repositories.sort_by_cached_key(|(_, repository)| {
+                // Dirty repositories come first since false is less than true
+                repository
+                    .status()
+                    .is_none_or(|status| status.to_string().is_empty())
+            }),
+            SortKey::Age => repositories.sort_by_cached_key(|(_, repository)| {
+                Reverse(repository.last_commit_time(self.commit_time))
+            }),
+        }
+        if self.reverse {
+            repositories.reverse();
+        }
 
-        // Format table
-        let format = format::FormatBuilder::new()
-            .column_separator(' ')
-            .borders(' ')
-            .padding(0, 3)
-            .build();
-        table.set_format(format);
+        if self.template.is_some() || self.format == OutputFormat::Json {
+            match &self.template {
+                Some(template) => {
+                    self.display_template(template, &repositories);
+                    if !report.is_empty() {
+                        eprint!("{}", report);
+                    }
+                }
+                // Errors are part of the output
+                None => self.display_json(&repositories, report),
+            }
+            return;
+        }
 
         // Add rows to table
-        for (name, repository) in sorted_map.iter() {
-            // Get status
-            let status = if let Some(status) = repository.status() {
-                status.to_string()
-            } else {
-                String::new()
-            };
-            // Get distance between local and upstream
-            let distance = if let Some(distance) = repository.distance() {
-                distance.to_string()
-            } else {
-                String::new()
+        for (name, repository) in repositories {
+            let cells = columns
+                .iter()
+                .map(|column| {
+                    let (value, color) = self.column_value(*column, name, repository, &tree_sizes);
+                    new_cell(&value, color)
+                })
+                .collect();
+            table.add_row(Row::new(cells));
+        }
+
+        // Display table
+        match self.format {
+            OutputFormat::Csv | OutputFormat::Tsv => {
+                let delimiter = if self.format == OutputFormat::Csv {
+                    ','
+                } else {
+                    '\t'
+                };
+                for row in table.row_iter() {
+                    let fields = row
+                        .iter()
+                        .map(|cell| quote_field(&cell.get_content(), delimiter))
+                        .collect::<Vec<_>>();
+                    println!("{}", fields.join(&delimiter.to_string()));
+                }
+            }
+            _ => self.print_table(&table),
+        }
+
+        // Display errors grouped by category
+        if !report.is_empty() {
+            eprint!("{}", report);
+        }
+    }
+    /// Whether a repository passes the `--dirty` and distance filters
+    fn is_shown(&self, repository: &Repository) -> bool {
+        if self.dirty {
+            let is_clean = repository
+                .status()
+                .is_none_or(|status| status.to_string().is_empty());
+            if is_clean && matches!(repository.distance(), Some(Distance::Same)) {
+                return false;
+            }
+        }
+        // Distance filters can be combined to show repositories in any of their states
+        if self.behind || self.ahead || self.diverged {
+            return match repository.distance() {
+                Some(Distance::Behind(_)) => self.behind,
+                Some(Distance::Ahead(_)) => self.ahead,
+                Some(Distance::Both(_, _)) => self.diverged,
+                _ => false,
             };
-            table.add_row(row![
-                name,
-                status,
-                repository.branch_name().unwrap_or_default().to_string(),
-                distance,
-                repository.remote_name().unwrap_or_default().to_string(),
+        }
+        true
+    }
+    /// Print a table with colors according to `--color`
+    fn print_table(&self, table: &Table) {
+        match self.color {
+            ColorChoice::Always => {
+                let _ = table.print_tty(true);
+            }
+            // Colors are only rendered when printing to a terminal
+            ColorChoice::Auto if env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()) => {
+                table.printstd();
+            }
+            _ => {
+                let _ = table.print(&mut io::stdout());
+            }
+        }
+    }
+    /// Print the row of a repository as soon as it is processed, padding its cells to the
+    /// widest ones so far to keep columns aligned
+    fn print_streamed_row(
+        &self,
+        columns: &[Column],
+        widths: &mut [usize],
+        repository: &Repository,
+    ) {
+        if !self.is_shown(repository) {
+            return;
+        }
+        let name = repository.name().to_string();
+        let cells = columns
+            .iter()
+            .zip(widths.iter_mut())
+            .map(|(column, width)| {
+                let (value, color) = self.column_value(*column, &name, repository, &HashMap::new());
+                *width = (*width).max(value.chars().count());
+                new_cell(&format!("{:<width$}", value, width = *width), color)
+            })
+            .collect();
+        let mut table = new_table();
+        table.add_row(Row::new(cells));
+        self.print_table(&table);
+    }
+    /// Initial widths of the columns of streamed rows, from what is known before processing
+    fn stream_widths(&self, columns: &[Column], repositories: &[Repository]) -> Vec<usize> {
+        columns
+            .iter()
+            .map(|column| match column {
+                // Same values as the rows, with their markers
+                Column::Name | Column::Branch | Column::Remote => repositories
+                    .iter()
+                    .map(|repository| {
+                        let name = repository.name().to_string();
+                        let (value, _) =
+                            self.column_value(*column, &name, repository, &HashMap::new());
+                        value.chars().count()
+                    })
+                    .max()
+                    .unwrap_or(0),
+                // Wide enough for `==` and ages such as `59m`
+                Column::Distance => 2,
+                Column::Age => 3,
+                _ => 0,
+            })
+            .collect()
+    }
+    /// Columns of the status table, the ones given with `--columns` or the default ones
+    fn columns(&self) -> Vec<Column> {
+        if !self.columns.is_empty() {
+            return self.columns.clone();
+        }
+        let mut columns = vec![
+            Column::Name,
+            Column::Status,
+            Column::Branch,
+            Column::Distance,
+            Column::Remote,
+            Column::Age,
+            Column::Stashes,
+        ];
+        // Optional columns go before the summary
+        if self.show_tree_size {
+            columns.push(Column::TreeSize);
+        }
+        if self.show_ignored {
+            columns.push(Column::Ignored);
+        }
+        columns.push(Column::Summary);
+        columns
+    }
+    /// Get the content of a column of the status table for a repository, along with its color
+    fn column_value(
+        &self,
+        column: Column,
+        name: &String,
+        repository: &Repository,
+        tree_sizes: &HashMap<&String, u64>,
+    ) -> (String, Option<color::Color>) {
+        let is_dirty = repository
+            .status()
+            .is_some_and(|status| !status.to_string().is_empty());
+        match column {
+            Column::Name => {
+                // Mark the name if commits would be authored with an unexpected email
+                let mut name = match self.expected_email(name) {
+                    Some(expected_email) if repository.detect_email_mismatch(expected_email) => {
+                        format!("{} [E]", name)
+                    }
+                    _ => name.clone(),
+                };
+                if repository
+                    .broken_refs()
+                    .is_some_and(|broken_refs| !broken_refs.is_empty())
+                {
+                    name.push_str(" [broken refs]");
+                }
+                let color = if is_dirty { None } else { Some(color::GREEN) };
+                (name, color)
+            }
+            Column::Status => {
+                let status = if let Some(status) = repository.status() {
+                    status.to_string()
+                } else {
+                    String::new()
+                };
+                // Follow the status with counts of problems
+                let mut indicators = vec![status];
+                if self.check_large_commits {
+                    let large_commits = repository
+                        .detect_large_commits(LARGE_COMMIT_THRESHOLD_MB * MEGABYTE)
+                        .len();
+                    if large_commits > 0 {
+                        indicators.push(format!("LGC:{}", large_commits));
+                    }
+                }
+                if self.check_eol {
+                    let crlf_files = repository.detect_non_lf_line_endings().len();
+                    if crlf_files > 0 {
+                        indicators.push(format!("CRLF:{}", crlf_files));
+                    }
+                }
+                // Non-ASCII paths are legitimate, they are only listed by `check-encoding`
+                if self.check_encoding
+                    && repository
+                        .detect_encoding_issues()
+                        .iter()
+                        .any(|(_, issue)| {
+                            matches!(issue, EncodingIssue::InvalidUtf8CommitMessage(_))
+                        })
+                {
+                    indicators.push("UTF8".to_string());
+                }
+                if self.check_binary {
+                    let binary_files = repository.detect_binary_files_in_index().len();
+                    if binary_files > 0 {
+                        indicators.push(format!("BIN:{}", binary_files));
+                    }
+                }
+                indicators.retain(|indicator| !indicator.is_empty());
+                let color = if is_dirty { Some(color::RED) } else { None };
+                (indicators.join(" "), color)
+            }
+            Column::Branch => {
+                // Mark the branch if it is not the expected one
+                let mut branch = repository.branch_name().unwrap_or_default();
+                if self.is_on_unexpected_branch(name, repository) {
+                    if let Some(expected_branch) = self.expected_branch(name) {
+                        branch = format!("{} [!{}]", branch, expected_branch);
+                    }
+                }
+                if repository.is_shallow() {
+                    branch.push_str(" [shallow]");
+                }
+                // Distances may be misleading when the history is grafted
+                if !repository.grafts().is_empty() {
+                    branch.push_str(" [grafted]");
+                }
+                (branch, None)
+            }
+            Column::Distance => {
+                let distance = repository.distance();
+                let color = match distance {
+                    Some(Distance::Behind(_)) => Some(color::YELLOW),
+                    _ => None,
+                };
+                let distance = distance
+                    .as_ref()
+                    .map(|distance| self.format_distance(distance))
+                    .unwrap_or_default();
+                (distance, color)
+            }
+            Column::Remote => (repository.remote_name().unwrap_or_default(), None),
+            Column::Age => (
+                repository
+                    .last_commit_time(self.commit_time)
+                    .map(|time| format_age(Utc::now().signed_duration_since(time)))
+                    .unwrap_or_default(),
+                None,
+            ),
+            // Streamed rows are displayed before the sizes of every repository are computed
+            Column::TreeSize => (
+                format_size(
+                    tree_sizes
+                        .get(name)
+                        .copied()
+                        .unwrap_or_else(|| repository.tree_size("HEAD").unwrap_or(0)),
+                ),
+                None,
+            ),
+            // Empty without stashes so that those of other repositories stand out
+            Column::Stashes => (
+                repository
+                    .stash_list()
+                    .ok()
+                    .filter(|stashes| !stashes.is_empty())
+                    .map(|stashes| format!("{} stashed", stashes.len()))
+                    .unwrap_or_default(),
+                None,
+            ),
+            Column::Ignored => (
+                repository
+                    .list_ignored_files()
+                    .map(|paths| format!("{} ignored", paths.len()))
+                    .unwrap_or_default(),
+                None,
+            ),
+            Column::Summary => (
                 repository
                     .commit_summary()
                     .unwrap_or_default()
                     .chars()
                     .take(50)
-                    .collect::<String>()
-            ]);
+                    .collect::<String>(),
+                None,
+            ),
+        }
+    }
+    /// Format a distance with its numbers of commits, or as a symbol with `--compact-distance`
+    fn format_distance(&self, distance: &Distance) -> String {
+        if self.compact_distance {
+            distance.symbol().to_string()
+        } else {
+            distance.to_string()
+        }
+    }
+    /// Print a line per repository rendered from `template`
+    fn display_template(&self, template: &Template, repositories: &[(&String, &Repository)]) {
+        for (name, repository) in repositories {
+            let mut line = String::new();
+            for part in &template.0 {
+                match part {
+                    TemplatePart::Literal(literal) => line.push_str(literal),
+                    TemplatePart::Field(field) => {
+                        let value = match field {
+                            TemplateField::Name => name.to_string(),
+                            TemplateField::Path => self
+                                .config
+                                .repositories()
+                                .get(*name)
+                                .map(|repository_config| {
+                                    repository_config.path.display().to_string()
+                                })
+                                .unwrap_or_default(),
+                            TemplateField::Branch => repository.branch_name().unwrap_or_default(),
+                            TemplateField::Status => repository
+                                .status()
+                                .map(|status| status.to_string())
+                                .unwrap_or_default(),
+                            TemplateField::Distance => repository
+                                .distance()
+                                .map(|distance| self.format_distance(&distance))
+                                .unwrap_or_default(),
+                            TemplateField::Remote => repository.remote_name().unwrap_or_default(),
+                            TemplateField::Age => repository
+                                .last_commit_time(self.commit_time)
+                                .map(|time| format_age(Utc::now().signed_duration_since(time)))
+                                .unwrap_or_default(),
+                            TemplateField::Summary => {
+                                repository.commit_summary().unwrap_or_default()
+                            }
+                        };
+                        line.push_str(&value);
+                    }
+                }
+            }
+            println!("{}", line);
+        }
+    }
+    /// Print the status of the repositories as a JSON array, one object per repository
+    fn display_json(&self, repositories: &[(&String, &Repository)], report: &ErrorReport) {
+        let repositories = repositories
+            .iter()
+            .map(|(name, repository)| {
+                let path = self
+                    .config
+                    .repositories()
+                    .get(*name)
+                    .map(|repository_config| repository_config.path.clone());
+                let status = repository.status().map(|status| {
+                    serde_json::json!({
+                        "staged": status.has_staged_files(),
+                        "unstaged": status.has_unstaged_files(),
+                        "untracked": status.has_untracked_files(),
+                    })
+                });
+                let distance = repository.distance();
+                let state = distance.as_ref().map(|distance| match distance {
+                    Distance::Same => "same",
+                    Distance::Ahead(_) => "ahead",
+                    Distance::Behind(_) => "behind",
+                    Distance::Both(_, _) => "diverged",
+                });
+                serde_json::json!({
+                    "name": name,
+                    "path": path,
+                    "branch": repository.branch_name(),
+                    "status": status,
+                    "distance": state,
+                    "ahead": distance.as_ref().map(Distance::ahead),
+                    "behind": distance.as_ref().map(Distance::behind),
+                    "remote": repository.remote_name(),
+                    "stashes": repository.stash_list().ok().map(|stashes| stashes.len()),
+                    "notes": repository.notes_list().ok().map(|notes| notes.len()),
+                    "summary": repository.commit_summary(),
+                    "author_time": repository
+                        .last_commit_time(TimeKind::Author)
+                        .map(|time| time.to_rfc3339()),
+                    "committer_time": repository
+                        .last_commit_time(TimeKind::Committer)
+                        .map(|time| time.to_rfc3339()),
+                    "diagnostics": repository.diagnose(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let output = serde_json::json!({
+            "repositories": repositories,
+            "errors": report.to_json(),
+        });
+        println!("{}", output);
+    }
+    /// Get the remote to push to, defaulting to the upstream's remote
+    fn push_remote(&self, name: &str, repository: &Repository) -> Result<String> {
+        self.config
+            .repositories()
+            .get(name)
+            .and_then(|repository_config| repository_config.push_remote.clone())
+            .or_else(|| repository.upstream_remote())
+            .ok_or_else(|| anyhow!("'{}' has no push remote nor upstream", name))
+    }
+    /// Whether a repository has any of the tags given with `--tag`, or none were given
+    fn has_tags(&self, name: &str) -> bool {
+        self.tags.is_empty()
+            || self
+                .config
+                .repositories()
+                .get(name)
+                .is_some_and(|repository| self.tags.iter().any(|tag| repository.tags.contains(tag)))
+    }
+    fn expected_branch(&self, name: &str) -> Option<&String> {
+        self.config
+            .repositories()
+            .get(name)
+            .and_then(|repository_config| repository_config.expected_branch.as_ref())
+    }
+    fn expected_email(&self, name: &str) -> Option<&String> {
+        self.config
+            .repositories()
+            .get(name)
+            .and_then(|repository_config| repository_config.expected_email.as_ref())
+    }
+    fn shallow(&self, name: &str) -> Option<u32> {
+        self.config
+            .repositories()
+            .get(name)
+            .and_then(|repository_config| repository_config.shallow)
+    }
+    fn is_on_unexpected_branch(&self, name: &str, repository: &Repository) -> bool {
+        match self.expected_branch(name) {
+            Some(expected_branch) => repository.branch_name().as_ref() != Some(expected_branch),
+            None => false,
         }
+    }
+    /// Run the interactive interface while repositories are processed again every `refresh`
+    /// or when requested, showing the last `commits` commits in the detail pane
+    fn run_tui(&self, refresh: Duration, commits: usize) -> Result<()> {
+        let columns = self.columns();
+        let headers = columns
+            .iter()
+            .map(|column| column.name().to_uppercase())
+            .collect();
+        let (update_tx, update_rx) = channel();
+        let (request_tx, request_rx) = channel();
+        let columns = &columns;
+        // Quitting does not wait for the repositories being processed
+        let cancelled = &AtomicBool::new(false);
+        thread::scope(|scope| {
+            scope.spawn(move || loop {
+                if update_tx.send(tui::Update::Refreshing).is_err() {
+                    break;
+                }
+                let mut report = ErrorReport::default();
+                let repositories = self.open_repositories(&mut report);
+                let sorted_map = self.process_until(repositories, &mut report, |_| {}, cancelled);
+                let rows = sorted_map
+                    .iter()
+                    .filter(|(_, repository)| self.is_shown(repository))
+                    .map(|(name, repository)| self.tui_row(columns, name, repository))
+                    .collect();
+                // Only show the summary line of the report
+                let errors = if report.is_empty() {
+                    None
+                } else {
+                    report.to_string().lines().next().map(String::from)
+                };
+                if update_tx.send(tui::Update::Rows(rows, errors)).is_err() {
+                    break;
+                }
 
-        // Display table
-        table.printstd();
+                // Handle actions on single repositories until the next refresh
+                let deadline = Instant::now() + refresh;
+                loop {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    let (name, pull) = match request_rx.recv_timeout(timeout) {
+                        Ok(tui::Request::Fetch(name)) => (name, false),
+                        Ok(tui::Request::Pull(name)) => (name, true),
+                        Ok(tui::Request::Refresh) | Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    };
+                    let message = match self.tui_action(&name, pull) {
+                        Ok((message, repository)) => {
+                            let row = self.tui_row(columns, &name, &repository);
+                            let _ = update_tx.send(tui::Update::Row(row));
+                            format!("{}: {}", name, message)
+                        }
+                        Err(e) => format!("{}: {:#}", name, e),
+                    };
+                    let _ = update_tx.send(tui::Update::Message(message));
+                }
+            });
+            let result = tui::run(headers, update_rx, request_tx, |name| {
+                self.repository_details(name, commits)
+                    .unwrap_or_else(|e| vec![format!("error: {:#}", e)])
+            });
+            cancelled.store(true, Ordering::Relaxed);
+            result
+        })
+    }
+    /// Build the row of a repository in the interactive interface
+    fn tui_row(&self, columns: &[Column], name: &String, repository: &Repository) -> tui::TableRow {
+        tui::TableRow {
+            name: name.clone(),
+            path: self.config.repositories()[name].path.clone(),
+            cells: columns
+                .iter()
+                .map(|column| {
+                    let (value, color) =
+                        self.column_value(*column, name, repository, &HashMap::new());
+                    (value, color.map(tui_color))
+                })
+                .collect(),
+        }
+    }
+    /// Fetch a repository and fast-forward its current branch if `pull`, returning a description
+    /// of the result along with the repository and its updated status
+    fn tui_action(&self, name: &str, pull: bool) -> Result<(String, Repository)> {
+        let mut repository = self.open_repository(name)?;
+        repository.fetch().context("failed to fetch")?;
+        let message = if pull {
+            match repository.fast_forward()? {
+                0 => "already up to date".to_string(),
+                count => format!("fast-forwarded {} commits", count),
+            }
+        } else {
+            "fetched".to_string()
+        };
+        repository.compute_status()?;
+        Ok((message, repository))
+    }
+    /// Describe a repository for the detail pane of the interactive interface
+    fn repository_details(&self, name: &str, commits: usize) -> Result<Vec<String>> {
+        let repository = self.open_repository(name)?;
+        let mut lines = vec![format!(
+            "branch: {}",
+            repository
+                .branch_name()
+                .unwrap_or_else(|| "(detached)".to_string())
+        )];
+        lines.push(String::new());
+        lines.push("recent commits:".to_string());
+        for commit in repository.recent_commits(commits)? {
+            lines.push(format!(
+                "  {} {} ({}, {})",
+                commit.short_id(),
+                commit.summary,
+                commit.author,
+                format_age(Utc::now().signed_duration_since(commit.time))
+            ));
+        }
+        let stashes = repository.stash_list()?;
+        if !stashes.is_empty() {
+            lines.push(String::new());
+            lines.push("stashes:".to_string());
+            for (i, stash) in stashes.iter().enumerate() {
+                lines.push(format!("  stash@{{{}}}: {}", i, stash.summary));
+            }
+        }
+        let changed_files = repository.changed_files()?;
+        if !changed_files.is_empty() {
+            lines.push(String::new());
+            lines.push("changed files:".to_string());
+            for (status, path) in changed_files {
+                lines.push(format!("  {} {}", short_status(status), path));
+            }
+        }
+        Ok(lines)
+    }
+    /// Print a detailed report of the state of a repository
+    fn print_repository_status(&self, name: &str) -> Result<()> {
+        let repository = self.open_repository(name)?;
+        println!(
+            "branch: {}",
+            repository
+                .branch_name()
+                .unwrap_or_else(|| "(detached)".to_string())
+        );
+        match (repository.remote_name(), repository.distance()) {
+            (Some(upstream), Some(distance)) => {
+                println!("upstream: {}", upstream);
+                println!("ahead: {}", distance.ahead());
+                println!("behind: {}", distance.behind());
+            }
+            _ => println!("upstream: (none)"),
+        }
+        if let Some(commit) = repository.recent_commits(1)?.first() {
+            println!(
+                "last commit: {} {} <{}> {} ({})",
+                commit.short_id(),
+                commit.author,
+                commit.email,
+                commit.time.format("%Y-%m-%d %H:%M"),
+                format_age(Utc::now().signed_duration_since(commit.time))
+            );
+            println!("    {}", commit.summary);
+        }
+
+        let changed_files = repository.changed_files()?;
+        let sections = [
+            ("conflicted", git2::Status::CONFLICTED),
+            (
+                "staged",
+                git2::Status::INDEX_NEW
+                    | git2::Status::INDEX_MODIFIED
+                    | git2::Status::INDEX_DELETED
+                    | git2::Status::INDEX_RENAMED
+                    | git2::Status::INDEX_TYPECHANGE,
+            ),
+            (
+                "unstaged",
+                git2::Status::WT_MODIFIED
+                    | git2::Status::WT_DELETED
+                    | git2::Status::WT_RENAMED
+                    | git2::Status::WT_TYPECHANGE,
+            ),
+            ("untracked", git2::Status::WT_NEW),
+        ];
+        for (title, flags) in sections.iter() {
+            let paths = changed_files
+                .iter()
+                .filter(|(status, _)| status.intersects(*flags))
+                .map(|(_, path)| path)
+                .collect::<Vec<_>>();
+            if !paths.is_empty() {
+                println!("{}:", title);
+                for path in paths {
+                    println!("    {}", path);
+                }
+            }
+        }
+
+        let stashes = repository.stash_list()?;
+        if !stashes.is_empty() {
+            println!("stashes:");
+            for (i, stash) in stashes.iter().enumerate() {
+                println!("    stash@{{{}}}: {}", i, stash.summary);
+            }
+        }
+        Ok(())
+    }
+    /// Watch the repositories and refresh the status of those whose files change
+    fn watch(&self, mut sorted_map: BTreeMap<String, Repository>) -> Result<()> {
+        let (tx, rx) = channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+
+        // Watch repository paths and fall back to polling those which cannot be watched
+        let mut watched = Vec::new();
+        let mut polled = Vec::new();
+        for name in sorted_map.keys() {
+            let path = &self.config.repositories()[name].path;
+            match path.canonicalize() {
+                Ok(path) if watcher.watch(&path, RecursiveMode::Recursive).is_ok() => {
+                    watched.push((path, name.clone()))
+                }
+                _ => polled.push(name.clone()),
+            }
+        }
+
+        let interval = Duration::from_secs(self.interval);
+        loop {
+            let mut changed = BTreeSet::new();
+            match rx.recv_timeout(interval) {
+                Ok(event) => {
+                    // Gather events arriving in a burst before refreshing
+                    let mut events = vec![event];
+                    while let Ok(event) = rx.recv_timeout(Duration::from_millis(200)) {
+                        events.push(event);
+                    }
+                    for event in events.into_iter().filter_map(|event| event.ok()) {
+                        if let EventKind::Access(_) = event.kind {
+                            continue;
+                        }
+                        for event_path in event.paths {
+                            if let Some((_, name)) = watched
+                                .iter()
+                                .find(|(path, _)| event_path.starts_with(path))
+                            {
+                                changed.insert(name.clone());
+                            }
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => changed.extend(polled.iter().cloned()),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if changed.is_empty() {
+                continue;
+            }
 
+            // Refresh changed repositories and redraw the table
+            let mut report = ErrorReport::default();
+            for name in changed {
+                if let Some(repository) = sorted_map.get_mut(&name) {
+                    if let Err(e) = repository.compute_status() {
+                        report.add(&name, &e.context("failed to compute status"));
+                    }
+                }
+            }
+            print!("\x1B[2J\x1B[1;1H");
+            self.display(&sorted_map, &report);
+        }
         Ok(())
     }
 }
+
+/// Create a table without borders
+fn new_table() -> Table {
+    let mut table = Table::new();
+    let format = format::FormatBuilder::new()
+        .column_separator(' ')
+        .borders(' ')
+        .padding(0, 3)
+        .build();
+    table.set_format(format);
+    table
+}
+
+/// Format the status of a file with two letters for the index and the working tree, as
+/// `git status --short` does
+fn short_status(status: git2::Status) -> String {
+    if status.is_wt_new() {
+        return "??".to_string();
+    }
+    if status.is_conflicted() {
+        return "UU".to_string();
+    }
+    let index = if status.is_index_new() {
+        'A'
+    } else if status.is_index_modified() {
+        'M'
+    } else if status.is_index_deleted() {
+        'D'
+    } else if status.is_index_renamed() {
+        'R'
+    } else if status.is_index_typechange() {
+        'T'
+    } else {
+        ' '
+    };
+    let worktree = if status.is_wt_modified() {
+        'M'
+    } else if status.is_wt_deleted() {
+        'D'
+    } else if status.is_wt_renamed() {
+        'R'
+    } else if status.is_wt_typechange() {
+        'T'
+    } else {
+        ' '
+    };
+    format!("{}{}", index, worktree)
+}
+
+/// Convert a color of the table to one of the interactive interface
+fn tui_color(color: color::Color) -> ratatui::style::Color {
+    match color {
+        color::RED => ratatui::style::Color::Red,
+        color::GREEN => ratatui::style::Color::Green,
+        color::YELLOW => ratatui::style::Color::Yellow,
+        _ => ratatui::style::Color::Reset,
+    }
+}
+
+/// Create a table cell, colored if `color` is set
+fn new_cell(content: &str, color: Option<color::Color>) -> Cell {
+    let mut cell = Cell::new(content);
+    if let Some(color) = color {
+        cell.style(Attr::ForegroundColor(color));
+    }
+    cell
+}
+
+/// Quote a field of a delimited row if it contains the delimiter, a quote or a line break,
+/// doubling its quotes as in RFC 4180
+fn quote_field(field: &str, delimiter: char) -> String {
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Format a duration compactly with its largest unit (e.g. `3d`)
+fn format_age(duration: chrono::Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+    match minutes {
+        m if m < 60 => format!("{}m", m),
+        m if m < 60 * 24 => format!("{}h", m / 60),
+        m if m < 60 * 24 * 7 => format!("{}d", m / (60 * 24)),
+        m if m < 60 * 24 * 30 => format!("{}w", m / (60 * 24 * 7)),
+        m if m < 60 * 24 * 365 => format!("{}mo", m / (60 * 24 * 30)),
+        m => format!("{}y", m / (60 * 24 * 365)),
+    }
+}
+
+/// Format a size in bytes with SI prefixes, e.g. `1.5 MB`
+fn format_size(size: u64) -> String {
+    const UNITS: &[&str] = &["kB", "MB", "GB", "TB"];
+    if size < 1000 {
+        return format!("{} B", size);
+    }
+    let mut value = size as f64 / 1000.0;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Create a command running `cmd` through the system shell
+fn shell_command(cmd: &str) -> process::Command {
+    if cfg!(windows) {
+        let mut command = process::Command::new("cmd");
+        command.args(["/C", cmd]);
+        command
+    } else {
+        let mut command = process::Command::new("sh");
+        command.args(["-c", cmd]);
+        command
+    }
+}
+
+/// Quote a value so that the shell of `shell_command` reads it as a single word
+fn shell_quote(value: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// Name of the directory a repository is cloned into, such as `repo` for `git@host:user/repo.git`
+fn clone_directory_name(url: &str) -> Result<&str> {
+    let url = url.trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    url.rsplit(['/', ':'])
+        .next()
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| anyhow!("cannot name the directory to clone '{}' into", url))
+}
+
+/// Split a range of commits of the form `FROM..TO`, where a missing side means HEAD
+fn split_range(range: &str) -> Result<(&str, &str)> {
+    let (from, to) = range
+        .split_once("..")
+        .context("range must be of the form FROM..TO")?;
+    let from = if from.is_empty() { "HEAD" } else { from };
+    let to = if to.is_empty() { "HEAD" } else { to };
+    Ok((from, to))
+}
+
+/// Find the repositories in the directory tree of `path` down to `max_depth`, without looking
+/// inside repositories nor following symbolic links
+fn find_repositories(path: &Path, max_depth: usize, found: &mut Vec<PathBuf>) -> Result<()> {
+    if path.join(".git").exists() {
+        found.push(path.to_owned());
+        return Ok(());
+    }
+    if max_depth == 0 {
+        return Ok(());
+    }
+    let mut entries = std::fs::read_dir(path)
+        .with_context(|| format!("failed to read '{}'", path.display()))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    entries.sort();
+    for entry in entries {
+        // Unreadable subdirectories are skipped rather than aborting the search
+        let _ = find_repositories(&entry, max_depth - 1, found);
+    }
+    Ok(())
+}
+
+/// Name the repository at `path` with a strategy, from its path alone if it cannot be opened
+fn detect_name(path: &Path, strategy: &NamingStrategy) -> Result<String> {
+    match Repository::open(path) {
+        Ok(repository) => repository.auto_detect_name(strategy),
+        Err(_) => strategy.name_from_path(path),
+    }
+}
+
+/// Print the completion script generated by clap, extended to complete the names of the
+/// repositories with `glim list --names`
+fn print_completions(shell: Shell) {
+    let bin_name = structopt::clap::crate_name!();
+    let mut script = Vec::new();
+    Cli::clap().gen_completions_to(bin_name, shell, &mut script);
+    let script = String::from_utf8_lossy(&script);
+    let names = format!("{} list --names 2>/dev/null", bin_name);
+    let commands = NAME_COMMANDS.join(" ");
+    let value_options = VALUE_OPTIONS.join("|");
+    match shell {
+        Shell::Bash => {
+            print!("{}", script);
+            // Wrap the generated function, which only completes files for positional arguments
+            println!(
+                r#"
+_{bin}_names() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" command="" i
+    # The subcommand is the first word which is neither an option nor the value of one
+    for (( i = 1; i < COMP_CWORD; i++ )); do
+        case "${{COMP_WORDS[i]}}" in
+            {value_options}) (( i++ )) ;;
+            -*) ;;
+            *) command="${{COMP_WORDS[i]}}"; break ;;
+        esac
+    done
+    if [[ -n "$command" && "$cur" != -* && " {commands} " == *" $command "* ]] ||
+        [[ "${{COMP_WORDS[COMP_CWORD-1]}}" == --filter ]]; then
+        COMPREPLY=($(compgen -W "$({names})" -- "$cur"))
+    else
+        _{bin}
+    fi
+}}
+complete -F _{bin}_names -o bashdefault -o default {bin}"#,
+                bin = bin_name,
+                value_options = value_options,
+                commands = commands,
+                names = names,
+            );
+        }
+        Shell::Zsh => {
+            // The generated script ends by calling its function, the wrapper is called instead
+            let invocation = format!("_{} \"$@\"", bin_name);
+            let script = script.trim_end();
+            print!("{}", script.strip_suffix(&invocation).unwrap_or(script));
+            println!(
+                r#"_{bin}_names() {{
+    local command="" i
+    # The subcommand is the first word which is neither an option nor the value of one
+    for (( i = 2; i < CURRENT; i++ )); do
+        case "${{words[i]}}" in
+            {value_options}) (( i++ )) ;;
+            -*) ;;
+            *) command="${{words[i]}}"; break ;;
+        esac
+    done
+    if [[ -n "$command" && "${{words[CURRENT]}}" != -* && " {commands} " == *" $command "* ]] ||
+        [[ "${{words[CURRENT-1]}}" == --filter ]]; then
+        local -a names
+        names=(${{(f)"$({names})"}})
+        _describe 'repository' names
+    else
+        _{bin} "$@"
+    fi
+}}
+
+compdef _{bin}_names {bin}
+_{bin}_names "$@""#,
+                bin = bin_name,
+                value_options = value_options,
+                commands = commands,
+                names = names,
+            );
+        }
+        Shell::Fish => {
+            print!("{}", script);
+            println!(
+                "complete -c {} -n \"__fish_seen_subcommand_from {}\" -f -a \"({})\"",
+                bin_name, commands, names
+            );
+            println!(
+                "complete -c {} -n \"__fish_seen_subcommand_from foreach\" -l filter -x -a \"({})\"",
+                bin_name, names
+            );
+        }
+        _ => print!("{}", script),
+    }
+}
+
+/// Check that a path is the root of a git repository
+fn check_is_repository(path: &Path) -> Result<()> {
+    if git2::Repository::open(path).is_ok() {
+        return Ok(());
+    }
+    match git2::Repository::discover(path) {
+        Ok(repository) => Err(anyhow!(
+            "'{}' is inside the repository '{}', add that path instead",
+            path.display(),
+            repository
+                .workdir()
+                .unwrap_or_else(|| repository.path())
+                .display()
+        )),
+        Err(_) => Err(anyhow!(
+            "'{}' is not a git repository (use --force to add it anyway)",
+            path.display()
+        )),
+    }
+}
+
+/// Ask the user for confirmation on the terminal
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
@@ -1,18 +1,112 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fmt;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use anyhow::{anyhow, Context, Result};
 use directories::ProjectDirs;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use structopt::clap::crate_name;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
     #[serde(skip)]
     path: PathBuf,
-    repositories: HashMap<String, PathBuf>,
+    /// Whether this config was combined with a profile and must not be saved
+    #[serde(skip)]
+    overlaid: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    workers: Option<usize>,
+    #[serde(
+        serialize_with = "serialize_repositories",
+        deserialize_with = "deserialize_repositories"
+    )]
+    repositories: HashMap<String, RepositoryConfig>,
+}
+
+/// Settings of a single repository
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RepositoryConfig {
+    pub path: PathBuf,
+    /// Branch the repository is expected to be on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_branch: Option<String>,
+    /// Remote to push to instead of the upstream's remote
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub push_remote: Option<String>,
+    /// Email the commits of the repository are expected to be authored with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_email: Option<String>,
+    /// Depth the repository is always fetched with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shallow: Option<u32>,
+    /// Tags grouping the repository with others, such as `work`
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+}
+
+impl RepositoryConfig {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+            expected_branch: None,
+            push_remote: None,
+            expected_email: None,
+            shallow: None,
+            tags: BTreeSet::new(),
+        }
+    }
+    /// Whether only the path is set, in which case the entry is stored as a plain string
+    fn is_path_only(&self) -> bool {
+        self.expected_branch.is_none()
+            && self.push_remote.is_none()
+            && self.expected_email.is_none()
+            && self.shallow.is_none()
+            && self.tags.is_empty()
+    }
+}
+
+/// Repository entry as written in the config file: either a path or a table of settings
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RepositoryEntry<T, U> {
+    Path(T),
+    Table(U),
+}
+
+fn serialize_repositories<S: Serializer>(
+    repositories: &HashMap<String, RepositoryConfig>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    // TOML requires plain values to come before tables
+    let (paths, tables): (Vec<_>, Vec<_>) = repositories
+        .iter()
+        .partition(|(_, repository)| repository.is_path_only());
+    serializer.collect_map(paths.into_iter().chain(tables).map(|(name, repository)| {
+        let entry = if repository.is_path_only() {
+            RepositoryEntry::Path(&repository.path)
+        } else {
+            RepositoryEntry::Table(repository)
+        };
+        (name, entry)
+    }))
+}
+
+fn deserialize_repositories<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, RepositoryConfig>, D::Error> {
+    let entries: HashMap<String, RepositoryEntry<PathBuf, RepositoryConfig>> =
+        HashMap::deserialize(deserializer)?;
+    Ok(entries
+        .into_iter()
+        .map(|(name, entry)| {
+            let repository = match entry {
+                RepositoryEntry::Path(path) => RepositoryConfig::new(path),
+                RepositoryEntry::Table(repository) => repository,
+            };
+            (name, repository)
+        })
+        .collect())
 }
 
 impl Config {
@@ -26,32 +120,120 @@ impl Config {
             }
             Err(_) => Ok(Self {
                 path: path.to_owned(),
+                overlaid: false,
+                workers: None,
                 repositories: HashMap::new(),
             }),
         }
     }
-    pub fn repositories(&self) -> &HashMap<String, PathBuf> {
+    /// Load the partial config of a profile, stored next to this config
+    pub fn profile(&self, name: &str) -> Result<Self> {
+        let mut path = self.path.clone();
+        path.pop();
+        let path = path.join("profiles").join(format!("{}.toml", name));
+        if !path.is_file() {
+            return Err(anyhow!("profile '{}' does not exist", path.display()));
+        }
+        Self::new(path)
+    }
+    /// Combine with `overlay`, whose fields take precedence, into a config which cannot be saved
+    pub fn with_overlay(mut self, overlay: Config) -> Config {
+        if overlay.workers.is_some() {
+            self.workers = overlay.workers;
+        }
+        self.repositories.extend(overlay.repositories);
+        self.overlaid = true;
+        self
+    }
+    pub fn workers(&self) -> Option<usize> {
+        self.workers
+    }
+    pub fn repositories(&self) -> &HashMap<String, RepositoryConfig> {
         &self.repositories
     }
-    pub fn add_repository<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+    /// Get the paths of the repositories sorted by name
+    pub fn to_path_list(&self) -> Vec<PathBuf> {
+        self.to_pairs()
+            .into_iter()
+            .map(|(_, path)| path.to_owned())
+            .collect()
+    }
+    /// Get the sorted names of the repositories
+    pub fn to_name_list(&self) -> Vec<String> {
+        self.to_pairs()
+            .into_iter()
+            .map(|(name, _)| name.to_owned())
+            .collect()
+    }
+    /// Get the names and paths of the repositories sorted by name
+    pub fn to_pairs(&self) -> Vec<(&str, &Path)> {
+        let mut pairs = self
+            .repositories
+            .iter()
+            .map(|(name, repository)| (name.as_str(), repository.path.as_path()))
+            .collect::<Vec<_>>();
+        pairs.sort_unstable_by_key(|(name, _)| *name);
+        pairs
+    }
+    pub fn add_repository<P: AsRef<Path>>(&mut self, name: &str, path: P) -> Result<()> {
         let path = path.as_ref();
-        let name = path
-            .components()
-            .last()
-            .ok_or_else(|| anyhow!("path is too short"))?
-            .as_os_str()
-            .to_str()
-            .ok_or_else(|| anyhow!("path is not valid UTF-8"))?;
         if !self.repositories.contains_key(name) {
-            self.repositories.insert(name.to_owned(), path.to_owned());
+            self.repositories
+                .insert(name.to_owned(), RepositoryConfig::new(path));
             Ok(())
         } else {
             Err(anyhow!("name '{}' already exists", name))
         }
     }
+    /// Tag a repository, returning whether it was not tagged already
+    pub fn add_tag(&mut self, name: &str, tag: &str) -> Result<bool> {
+        let repository = self
+            .repositories
+            .get_mut(name)
+            .ok_or_else(|| anyhow!("name '{}' does not exist", name))?;
+        Ok(repository.tags.insert(tag.to_owned()))
+    }
+    /// Set the depth a repository is always fetched with
+    pub fn set_shallow(&mut self, name: &str, depth: u32) -> Result<()> {
+        let repository = self
+            .repositories
+            .get_mut(name)
+            .ok_or_else(|| anyhow!("name '{}' does not exist", name))?;
+        repository.shallow = Some(depth);
+        Ok(())
+    }
+    /// Untag a repository, returning whether it was tagged
+    pub fn remove_tag(&mut self, name: &str, tag: &str) -> Result<bool> {
+        let repository = self
+            .repositories
+            .get_mut(name)
+            .ok_or_else(|| anyhow!("name '{}' does not exist", name))?;
+        Ok(repository.tags.remove(tag))
+    }
     pub fn remove_repository_by_name(&mut self, name: &str) -> bool {
         self.repositories.remove(name).is_some()
     }
+    /// Get the sorted names of the repositories whose path cannot be reached, including broken
+    /// symlinks
+    pub fn unreachable_paths(&self) -> Vec<String> {
+        // Unlike `Path::exists` on some platforms, `metadata` follows symlinks and fails on broken ones
+        let mut names = self
+            .repositories
+            .iter()
+            .filter(|(_, repository)| std::fs::metadata(&repository.path).is_err())
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+    /// Remove the repositories whose path cannot be reached, returning their names
+    pub fn prune_unreachable_paths(&mut self) -> Vec<String> {
+        let names = self.unreachable_paths();
+        for name in &names {
+            self.repositories.remove(name);
+        }
+        names
+    }
     pub fn rename_repository(&mut self, name: &str, new_name: &str) -> Result<()> {
         if !self.repositories.contains_key(name) {
             Err(anyhow!("name '{}' does not exist", name))
@@ -67,6 +249,9 @@ impl Config {
         }
     }
     pub fn save(&self) -> Result<()> {
+        if self.overlaid {
+            return Err(anyhow!("cannot save a config combined with a profile"));
+        }
         let mut path = self.path.clone();
         if path.pop() {
             // Create config directory if it doesn't exist
@@ -89,6 +274,8 @@ impl Default for Config {
         let default_config_path = project_dirs.config_dir().join("config.toml");
         Self {
             path: default_config_path,
+            overlaid: false,
+            workers: None,
             repositories: HashMap::new(),
         }
     }
@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Multi-repository manager whose repositories can be imported
+#[derive(Clone, Copy)]
+pub enum ImportFormat {
+    /// `repos.csv` of gita
+    Gita,
+    /// `.mrconfig` of myrepos
+    Myrepos,
+    /// Output of `ghq list`, with or without `--full-path`
+    Ghq,
+    /// XML manifest of repo
+    Repo,
+    /// Manifest written by `glim export`
+    Glim,
+}
+
+/// Repository listed in a file
+pub struct ImportedRepository {
+    pub name: String,
+    pub path: PathBuf,
+    /// URL the repository can be cloned from, if it is known
+    pub url: Option<String>,
+    /// Branch checked out when the manifest was written by `glim freeze`, unless HEAD was detached
+    pub branch: Option<String>,
+    /// Commit HEAD pointed to when the manifest was written by `glim freeze`
+    pub commit: Option<String>,
+}
+
+/// Portable list of repositories written by `glim export`
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    repositories: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestEntry {
+    /// Path relative to the root given to `glim export`, unless it was outside of it
+    path: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    commit: Option<String>,
+}
+
+impl FromStr for ImportFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gita" => Ok(ImportFormat::Gita),
+            "myrepos" => Ok(ImportFormat::Myrepos),
+            "ghq" => Ok(ImportFormat::Ghq),
+            "repo" => Ok(ImportFormat::Repo),
+            "glim" => Ok(ImportFormat::Glim),
+            _ => Err("expected 'gita', 'myrepos', 'ghq', 'repo' or 'glim'"),
+        }
+    }
+}
+
+/// Read the repositories listed in `file`, the relative paths of a manifest being relative to
+/// `root`, the home directory by default
+///
+/// A manifest is read as JSON if the file has a `.json` extension, and as TOML otherwise.
+pub fn read_repositories(
+    format: ImportFormat,
+    file: &Path,
+    root: Option<&Path>,
+) -> Result<Vec<ImportedRepository>> {
+    let content =
+        fs::read_to_string(file).with_context(|| format!("failed to read '{}'", file.display()))?;
+    if let ImportFormat::Glim = format {
+        let manifest: Manifest = if is_json(file) {
+            serde_json::from_str(&content)?
+        } else {
+            toml::from_str(&content)?
+        };
+        let root = root.map_or_else(home_dir, Path::to_path_buf);
+        return Ok(manifest
+            .repositories
+            .into_iter()
+            .map(|(name, entry)| ImportedRepository {
+                name,
+                path: root.join(entry.path),
+                url: entry.url,
+                branch: entry.branch,
+                commit: entry.commit,
+            })
+            .collect());
+    }
+    // Relative paths are relative to the directory of the file, except for ghq and repo
+    let directory = file
+        .canonicalize()?
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let repositories = match format {
+        ImportFormat::Gita => parse_gita(&content),
+        ImportFormat::Myrepos => parse_myrepos(&content, &directory),
+        ImportFormat::Ghq => parse_ghq(&content, &ghq_root()),
+        ImportFormat::Repo => parse_repo(&content, &repo_root(&directory)),
+        ImportFormat::Glim => unreachable!(),
+    };
+    if repositories.is_empty() {
+        return Err(anyhow!("no repositories found in '{}'", file.display()));
+    }
+    Ok(repositories
+        .into_iter()
+        .map(|(name, path)| ImportedRepository {
+            name,
+            path,
+            url: None,
+            branch: None,
+            commit: None,
+        })
+        .collect())
+}
+
+/// Write the names, paths, URLs and checked out commits of repositories to a manifest, the paths
+/// being made relative to `root`, the home directory by default
+///
+/// The manifest is written as JSON if the file has a `.json` extension, and as TOML otherwise.
+pub fn write_manifest(
+    file: &Path,
+    root: Option<&Path>,
+    repositories: Vec<ImportedRepository>,
+) -> Result<()> {
+    let root = root.map_or_else(home_dir, Path::to_path_buf);
+    let manifest = Manifest {
+        repositories: repositories
+            .into_iter()
+            .map(|repository| {
+                let path = repository
+                    .path
+                    .strip_prefix(&root)
+                    .map(Path::to_path_buf)
+                    .unwrap_or(repository.path);
+                let entry = ManifestEntry {
+                    path,
+                    url: repository.url,
+                    branch: repository.branch,
+                    commit: repository.commit,
+                };
+                (repository.name, entry)
+            })
+            .collect(),
+    };
+    let content = if is_json(file) {
+        serde_json::to_string_pretty(&manifest)?
+    } else {
+        toml::to_string(&manifest)?
+    };
+    fs::write(file, content).with_context(|| format!("failed to write '{}'", file.display()))
+}
+
+/// Parse lines of `path,name,flags`, the name and flags being optional in older versions
+fn parse_gita(content: &str) -> Vec<(String, PathBuf)> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = line.split(',');
+            let path = PathBuf::from(fields.next().unwrap_or_default().trim());
+            let name = match fields.next().map(str::trim) {
+                Some(name) if !name.is_empty() => name.to_string(),
+                _ => last_components(&path, 1),
+            };
+            (name, path)
+        })
+        .collect()
+}
+
+/// Parse the sections of an INI file, each named after the path of a repository
+fn parse_myrepos(content: &str, directory: &Path) -> Vec<(String, PathBuf)> {
+    content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix('[')?.strip_suffix(']'))
+        .filter(|section| *section != "DEFAULT")
+        .map(|section| {
+            let path = directory.join(expand_home(section));
+            (last_components(&path, 1), path)
+        })
+        .collect()
+}
+
+/// Parse lines of `host/user/repo`, relative to the root of ghq, or of full paths
+fn parse_ghq(content: &str, root: &Path) -> Vec<(String, PathBuf)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let path = root.join(line);
+            (last_components(&path, 2), path)
+        })
+        .collect()
+}
+
+/// Parse the `<project>` elements of a manifest, whose path defaults to their name
+fn parse_repo(content: &str, root: &Path) -> Vec<(String, PathBuf)> {
+    content
+        .split("<project")
+        .skip(1)
+        .filter_map(|element| {
+            let tag = element.split('>').next()?;
+            let name = xml_attribute(tag, "path").or_else(|| xml_attribute(tag, "name"))?;
+            Some((name.clone(), root.join(name)))
+        })
+        .collect()
+}
+
+/// Get the value of an attribute in the content of a tag
+fn xml_attribute(tag: &str, key: &str) -> Option<String> {
+    let mut rest = tag;
+    while let Some(start) = rest.find(key) {
+        let preceded_by_space = rest[..start].ends_with(char::is_whitespace);
+        rest = &rest[start + key.len()..];
+        let value = match rest.trim_start().strip_prefix('=') {
+            Some(value) if preceded_by_space => value.trim_start(),
+            _ => continue,
+        };
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &value[1..];
+        return value
+            .find(quote)
+            .map(|end| value[..end].replace("&amp;", "&"));
+    }
+    None
+}
+
+/// Root of ghq, which is `~/ghq` unless `GHQ_ROOT` is set
+fn ghq_root() -> PathBuf {
+    match env::var("GHQ_ROOT") {
+        Ok(root) => PathBuf::from(root),
+        Err(_) => home_dir().join("ghq"),
+    }
+}
+
+/// Directory containing the `.repo` directory the manifest is in, or the directory of the
+/// manifest
+fn repo_root(directory: &Path) -> PathBuf {
+    directory
+        .ancestors()
+        .find(|ancestor| ancestor.file_name().is_some_and(|name| name == ".repo"))
+        .and_then(Path::parent)
+        .unwrap_or(directory)
+        .to_path_buf()
+}
+
+fn is_json(file: &Path) -> bool {
+    file.extension()
+        .is_some_and(|extension| extension == "json")
+}
+
+fn home_dir() -> PathBuf {
+    PathBuf::from(env::var("HOME").unwrap_or_default())
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(path) => home_dir().join(path),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Join the last `count` components of a path with slashes
+fn last_components(path: &Path, count: usize) -> String {
+    let mut components = path
+        .iter()
+        .rev()
+        .take(count)
+        .map(|component| component.to_string_lossy())
+        .collect::<Vec<_>>();
+    components.reverse();
+    components.join("/")
+}
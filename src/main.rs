@@ -1,13 +1,17 @@
 mod cli;
 mod config;
+mod import;
+mod open;
+mod report;
 mod repository;
+mod tui;
 
-use cli::CLI;
+use cli::Cli;
 
 use anyhow::Result;
 use structopt::StructOpt;
 
 fn main() -> Result<()> {
-    let mut cli = CLI::from_args();
+    let mut cli = Cli::from_args();
     cli.run()
 }
@@ -0,0 +1,48 @@
+use std::env;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use anyhow::{anyhow, Result};
+
+/// Open a directory or a URL with the default application of the system, without waiting for it
+/// to exit
+pub fn open_with_system<S: AsRef<OsStr>>(target: S) -> Result<()> {
+    let (program, args): (&str, &[&str]) = if cfg!(target_os = "macos") {
+        ("open", &[])
+    } else if cfg!(windows) {
+        // The empty argument is the title of the window, which `start` takes from the first
+        // quoted argument
+        ("cmd", &["/C", "start", ""])
+    } else {
+        ("xdg-open", &[])
+    };
+    let mut child = Command::new(program)
+        .args(args)
+        .arg(target)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| anyhow!("failed to run {}: {}", program, e))?;
+    // Reap the child once it exits so that it does not linger as a zombie
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Open a directory with `$VISUAL` or `$EDITOR`, falling back to vi, and wait for it to exit
+pub fn open_in_editor(path: &Path) -> Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // The editor may be given with arguments, such as `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    Command::new(program)
+        .args(words)
+        .arg(path)
+        .current_dir(path)
+        .status()
+        .map_err(|e| anyhow!("failed to run {}: {}", program, e))?;
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use crate::repository::BrokenReferences;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+
+use git2::{ErrorClass, ErrorCode};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ErrorCategory {
+    Auth,
+    Network,
+    NotFound,
+    Permission,
+    Corrupt,
+    Other,
+}
+
+impl ErrorCategory {
+    /// Find the category of an error by looking for a git or I/O error in its chain
+    pub fn of(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if cause.is::<BrokenReferences>() {
+                return ErrorCategory::Corrupt;
+            }
+            if let Some(error) = cause.downcast_ref::<git2::Error>() {
+                return Self::of_git(error);
+            }
+            if let Some(error) = cause.downcast_ref::<io::Error>() {
+                return Self::of_io(error);
+            }
+        }
+        ErrorCategory::Other
+    }
+    fn of_git(error: &git2::Error) -> Self {
+        match (error.code(), error.class()) {
+            (ErrorCode::Auth, _) => ErrorCategory::Auth,
+            (ErrorCode::NotFound, _) => ErrorCategory::NotFound,
+            (ErrorCode::Certificate, _)
+            | (_, ErrorClass::Net)
+            | (_, ErrorClass::Http)
+            | (_, ErrorClass::Ssl)
+            | (_, ErrorClass::Ssh) => ErrorCategory::Network,
+            (_, ErrorClass::Os) if error.message().to_lowercase().contains("permission denied") => {
+                ErrorCategory::Permission
+            }
+            (_, ErrorClass::Odb)
+            | (_, ErrorClass::Object)
+            | (_, ErrorClass::Zlib)
+            | (_, ErrorClass::Index)
+            | (_, ErrorClass::Tree) => ErrorCategory::Corrupt,
+            _ => ErrorCategory::Other,
+        }
+    }
+    fn of_io(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::PermissionDenied => ErrorCategory::Permission,
+            io::ErrorKind::NotFound => ErrorCategory::NotFound,
+            _ => ErrorCategory::Other,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorCategory::Auth => "auth",
+            ErrorCategory::Network => "network",
+            ErrorCategory::NotFound => "not-found",
+            ErrorCategory::Permission => "permission",
+            ErrorCategory::Corrupt => "corrupt",
+            ErrorCategory::Other => "other",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Errors of all repositories grouped by category
+#[derive(Default)]
+pub struct ErrorReport {
+    errors: BTreeMap<ErrorCategory, Vec<(String, String)>>,
+}
+
+impl ErrorReport {
+    pub fn add(&mut self, name: &str, error: &anyhow::Error) {
+        self.errors
+            .entry(ErrorCategory::of(error))
+            .or_default()
+            .push((name.to_string(), format!("{:#}", error)));
+    }
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+    pub fn len(&self) -> usize {
+        self.errors.values().map(Vec::len).sum()
+    }
+    /// Get the errors as a JSON object whose keys are the categories
+    pub fn to_json(&self) -> serde_json::Value {
+        self.errors
+            .iter()
+            .map(|(category, errors)| {
+                let mut names = errors
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<_>>();
+                names.sort_unstable();
+                names.dedup();
+                let messages = errors
+                    .iter()
+                    .map(|(name, message)| serde_json::json!({"name": name, "message": message}))
+                    .collect::<Vec<_>>();
+                let value = serde_json::json!({
+                    "count": errors.len(),
+                    "repositories": names,
+                    "errors": messages,
+                });
+                (category.to_string(), value)
+            })
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+}
+
+impl fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let counts = self
+            .errors
+            .iter()
+            .map(|(category, errors)| format!("{} {}", errors.len(), category))
+            .collect::<Vec<_>>();
+        writeln!(f, "{} errors: {}", self.len(), counts.join(", "))?;
+        for (category, errors) in self.errors.iter() {
+            let mut names = errors
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>();
+            names.sort_unstable();
+            names.dedup();
+            writeln!(f, "{} ({}): {}", category, errors.len(), names.join(", "))?;
+            for (name, message) in errors {
+                writeln!(f, "    {}: {}", name, message)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,57 +1,1924 @@
 use anyhow::{anyhow, Result};
+use chrono::{DateTime, FixedOffset, TimeZone};
 use git2::Status as FileStatus;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt;
-use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use threadpool::ThreadPool;
 
 pub struct Repository {
     inner: Arc<Mutex<git2::Repository>>,
     name: String,
     status: Option<Status>,
+    broken_refs: Option<Vec<String>>,
 }
 
 impl Repository {
-    pub fn open<P: AsRef<Path>>(name: &str, path: P) -> Result<Self> {
+    /// Open the repository at `path` under `name`
+    pub fn open_with_name<P: AsRef<Path>>(name: &str, path: P) -> Result<Self> {
         let repository = git2::Repository::open(path)?;
         Ok(Self {
             inner: Arc::new(Mutex::new(repository)),
             name: name.to_string(),
             status: None,
+            broken_refs: None,
         })
     }
+    /// Open the repository at `path`, named after the last component of its path
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let name = NamingStrategy::LastComponent.name_from_path(path.as_ref())?;
+        Self::open_with_name(&name, path)
+    }
+    /// Clone the repository at `url` into `path`
+    pub fn clone_with_name<P: AsRef<Path>>(name: &str, url: &str, path: P) -> Result<Self> {
+        let mut fo = git2::FetchOptions::new();
+        fo.remote_callbacks(remote_callbacks());
+        let repository = git2::build::RepoBuilder::new()
+            .fetch_options(fo)
+            .clone(url, path.as_ref())?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(repository)),
+            name: name.to_string(),
+            status: None,
+            broken_refs: None,
+        })
+    }
+    /// List the references contained in a bundle by reading its header
+    pub fn open_bundle(bundle_path: &Path) -> Result<Vec<(git2::Oid, String)>> {
+        let file = std::fs::File::open(bundle_path)?;
+        let mut lines = std::io::BufReader::new(file).split(b'\n');
+        let signature = lines.next().transpose()?.unwrap_or_default();
+        if !signature.starts_with(b"# v2 git bundle") && !signature.starts_with(b"# v3 git bundle")
+        {
+            return Err(anyhow!("'{}' is not a git bundle", bundle_path.display()));
+        }
+        let mut refs = Vec::new();
+        // The header ends with an empty line before the pack data
+        for line in lines {
+            let line = line?;
+            if line.is_empty() {
+                break;
+            }
+            let line = String::from_utf8_lossy(&line);
+            // Skip capabilities of v3 bundles and prerequisite commits
+            if line.starts_with('@') || line.starts_with('-') {
+                continue;
+            }
+            let (oid, name) = line
+                .split_once(' ')
+                .ok_or_else(|| anyhow!("invalid bundle reference '{}'", line))?;
+            refs.push((git2::Oid::from_str(oid)?, name.to_string()));
+        }
+        Ok(refs)
+    }
+    /// Create a bundle of references with `git bundle`, all of them if `refs` is empty
+    pub fn create_bundle(&self, output: &Path, refs: &[&str]) -> Result<()> {
+        let git_dir = {
+            let inner = self.inner.lock().unwrap();
+            inner.path().to_owned()
+        };
+        let mut command = process::Command::new("git");
+        command
+            .args(["bundle", "create"])
+            .arg(std::env::current_dir()?.join(output))
+            .current_dir(git_dir);
+        if refs.is_empty() {
+            command.arg("--all");
+        } else {
+            command.args(refs);
+        }
+        let output = command.output()?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "git bundle failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+    /// Pack references and objects and prune unreachable ones with `git gc`, returning the size
+    /// of the git directory before and after
+    pub fn gc(&self, aggressive: bool) -> Result<(u64, u64)> {
+        let git_dir = {
+            let inner = self.inner.lock().unwrap();
+            inner.path().to_owned()
+        };
+        let before = directory_size(&git_dir);
+        let mut command = process::Command::new("git");
+        command.args(["gc", "--quiet"]).current_dir(&git_dir);
+        if aggressive {
+            command.arg("--aggressive");
+        }
+        let output = command.output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "git gc failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok((before, directory_size(&git_dir)))
+    }
     pub fn name(&self) -> &str {
         &self.name
     }
-    pub fn fetch(&self) -> Result<()> {
+    /// Infer a name for the repository following `strategy`
+    pub fn auto_detect_name(&self, strategy: &NamingStrategy) -> Result<String> {
+        let inner = self.inner.lock().unwrap();
+        let path = inner.workdir().unwrap_or_else(|| inner.path());
+        if let NamingStrategy::RemoteSlug = strategy {
+            let remote_name = inner
+                .head()
+                .ok()
+                .and_then(|head| inner.branch_upstream_remote(head.name()?).ok())
+                .and_then(|remote_name| remote_name.as_str().map(String::from))
+                .unwrap_or_else(|| "origin".to_string());
+            let slug = inner
+                .find_remote(&remote_name)
+                .ok()
+                .and_then(|remote| remote_slug(remote.url()?));
+            if let Some(slug) = slug {
+                return Ok(slug);
+            }
+        }
+        strategy.name_from_path(path)
+    }
+    pub fn fetch(&self) -> Result<()> {
+        self.fetch_with_progress(|_, _| {})
+    }
+    /// Fetch the upstream of the current branch, reporting `(received_objects, total_objects)`
+    /// to `progress_fn` as the transfer advances
+    pub fn fetch_with_progress<F>(&self, progress_fn: F) -> Result<()>
+    where
+        F: Fn(u64, u64) + Send,
+    {
+        let inner = self.inner.lock().unwrap();
+        let head = inner.head()?;
+        // Nothing to fetch on a detached HEAD
+        if !head.is_branch() {
+            return Ok(());
+        }
+        let local_name = head
+            .name()
+            .ok_or_else(|| anyhow!("local name is not valid UTF-8"))?
+            .to_owned();
+        let remote_name = match inner.branch_upstream_remote(&local_name) {
+            Ok(remote_name) => remote_name,
+            // Nothing to fetch if the branch does not track a remote
+            Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut remote = inner.find_remote(
+            remote_name
+                .as_str()
+                .ok_or_else(|| anyhow!("remote name is not valid UTF-8"))?,
+        )?;
+
+        let mut callbacks = remote_callbacks();
+        callbacks.transfer_progress(|progress| {
+            progress_fn(
+                progress.received_objects() as u64,
+                progress.total_objects() as u64,
+            );
+            true
+        });
+        let mut fo = git2::FetchOptions::new();
+        fo.remote_callbacks(callbacks);
+
+        // Fetch
+        Ok(remote.fetch(&[&local_name], Some(&mut fo), None)?)
+    }
+    /// Fetch a tag from the remote tracked by the current branch, unless it is already present
+    /// locally and `force` is not set
+    pub fn fetch_tag(&self, name: &str, force: bool) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let refname = format!("refs/tags/{}", name);
+        if !force && inner.find_reference(&refname).is_ok() {
+            return Ok(());
+        }
+        let head = inner.head()?;
+        let remote_name = inner
+            .branch_upstream_remote(
+                head.name()
+                    .ok_or_else(|| anyhow!("local name is not valid UTF-8"))?,
+            )
+            .map_err(|_| anyhow!("current branch has no upstream"))?;
+        let mut remote = inner.find_remote(
+            remote_name
+                .as_str()
+                .ok_or_else(|| anyhow!("remote name is not valid UTF-8"))?,
+        )?;
+        let mut fo = git2::FetchOptions::new();
+        fo.remote_callbacks(remote_callbacks());
+        let refspec = format!("{}{1}:{1}", if force { "+" } else { "" }, refname);
+        remote.fetch(&[&refspec], Some(&mut fo), None)?;
+        // The fetch succeeds even if the remote does not have the tag
+        inner.find_reference(&refname).map_err(|_| {
+            anyhow!(
+                "tag '{}' does not exist on '{}'",
+                name,
+                remote.name().unwrap_or_default()
+            )
+        })?;
+        Ok(())
+    }
+    /// Fetch the upstream of the current branch with its history truncated to `n` commits, or
+    /// the complete history if `unshallow`
+    pub fn fetch_with_depth(&self, n: u32, unshallow: bool) -> Result<()> {
+        // Fetching with a depth is not supported by libgit2
+        let workdir = {
+            let inner = self.inner.lock().unwrap();
+            // Nothing to unshallow in a complete repository
+            if unshallow && !inner.is_shallow() {
+                return Ok(());
+            }
+            inner
+                .workdir()
+                .ok_or_else(|| anyhow!("repository is bare"))?
+                .to_owned()
+        };
+        let remote = match self.upstream_remote() {
+            Some(remote) => remote,
+            None => return Ok(()),
+        };
+        let mut command = process::Command::new("git");
+        command.arg("fetch").current_dir(workdir);
+        if unshallow {
+            command.arg("--unshallow");
+        } else if n > 0 {
+            command.arg(format!("--depth={}", n));
+        }
+        let output = command.arg(remote).output()?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "git fetch failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+    pub fn is_shallow(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.is_shallow()
+    }
+    /// Fetch the head of a GitHub pull request to `refs/remotes/<remote>/pr/<pr_number>`
+    pub fn fetch_pull_request(&self, pr_number: u32, remote: &str) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let mut remote = inner
+            .find_remote(remote)
+            .map_err(|_| anyhow!("remote '{}' does not exist", remote))?;
+        let refname = format!(
+            "refs/remotes/{}/pr/{}",
+            remote.name().unwrap_or_default(),
+            pr_number
+        );
+        let refspec = format!("+refs/pull/{}/head:{}", pr_number, refname);
+        let mut fo = git2::FetchOptions::new();
+        fo.remote_callbacks(remote_callbacks());
+        remote.fetch(&[&refspec], Some(&mut fo), None)?;
+        // The fetch succeeds even if the remote does not have the pull request
+        inner
+            .find_reference(&refname)
+            .map_err(|_| anyhow!("pull request #{} does not exist", pr_number))?;
+        Ok(())
+    }
+    /// Fetch only `branch` from `remote` to its remote-tracking branch, leaving the checkout
+    /// untouched
+    pub fn fetch_specific_branch(&self, branch: &str, remote: &str) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let mut remote = inner
+            .find_remote(remote)
+            .map_err(|_| anyhow!("remote '{}' does not exist", remote))?;
+        let refname = format!(
+            "refs/remotes/{}/{}",
+            remote.name().unwrap_or_default(),
+            branch
+        );
+        let refspec = format!("+refs/heads/{}:{}", branch, refname);
+        let mut fo = git2::FetchOptions::new();
+        fo.remote_callbacks(remote_callbacks());
+        remote.fetch(&[&refspec], Some(&mut fo), None)?;
+        // The fetch succeeds even if the remote does not have the branch
+        inner
+            .find_reference(&refname)
+            .map_err(|_| anyhow!("branch '{}' does not exist on the remote", branch))?;
+        Ok(())
+    }
+    /// Fetch `branch` from `upstream_remote` and fast-forward the local branch to it, only
+    /// checking whether a merge would conflict if their histories diverged
+    pub fn sync_fork(&self, upstream_remote: &str, branch: &str) -> Result<SyncResult> {
+        let inner = self.inner.lock().unwrap();
+        let tracking_name = format!("refs/remotes/{}/{}", upstream_remote, branch);
+        let old_tip = inner.refname_to_id(&tracking_name).ok();
+        let mut remote = inner
+            .find_remote(upstream_remote)
+            .map_err(|_| anyhow!("remote '{}' does not exist", upstream_remote))?;
+        let mut fo = git2::FetchOptions::new();
+        fo.remote_callbacks(remote_callbacks());
+        let refspec = format!("+refs/heads/{}:{}", branch, tracking_name);
+        remote.fetch(&[&refspec], Some(&mut fo), None)?;
+        let upstream_oid = inner
+            .refname_to_id(&tracking_name)
+            .map_err(|_| anyhow!("'{}' has no branch '{}'", upstream_remote, branch))?;
+        let reference = inner
+            .find_branch(branch, git2::BranchType::Local)?
+            .into_reference();
+        let (ahead, behind) =
+            fast_forward_reference(&inner, &reference, upstream_oid, "glim: sync fork")?;
+        let mut result = SyncResult {
+            fetched: old_tip != Some(upstream_oid),
+            ahead,
+            behind,
+            merged_commits: 0,
+            conflicts: false,
+        };
+        if ahead == 0 {
+            result.merged_commits = behind;
+        } else if behind > 0 {
+            let local_oid = reference
+                .target()
+                .ok_or_else(|| anyhow!("'{}' is not a direct reference", branch))?;
+            let index = inner.merge_commits(
+                &inner.find_commit(local_oid)?,
+                &inner.find_commit(upstream_oid)?,
+                None,
+            )?;
+            result.conflicts = index.has_conflicts();
+        }
+        Ok(result)
+    }
+    /// Fast-forward the current branch to its upstream, returning the number of new commits
+    pub fn fast_forward(&self) -> Result<usize> {
+        let inner = self.inner.lock().unwrap();
+        let head = inner.head()?;
+        if !head.is_branch() {
+            return Err(anyhow!("HEAD is detached"));
+        }
+        let local_name = head
+            .name()
+            .ok_or_else(|| anyhow!("local name is not valid UTF-8"))?;
+        let upstream_name = inner.branch_upstream_name(local_name)?;
+        let upstream_name = upstream_name
+            .as_str()
+            .ok_or_else(|| anyhow!("upstream name is not valid UTF-8"))?;
+        let upstream_oid = inner.refname_to_id(upstream_name)?;
+        match fast_forward_reference(&inner, &head, upstream_oid, "glim: fast-forward")? {
+            (ahead, behind) if ahead > 0 && behind > 0 => Err(anyhow!(
+                "cannot fast-forward, the branch diverged from its upstream"
+            )),
+            (_, behind) => Ok(behind),
+        }
+    }
+    /// Get the target of a tag along with its message if it is annotated
+    pub fn tag_target(&self, name: &str) -> Result<(git2::Oid, Option<String>)> {
+        let inner = self.inner.lock().unwrap();
+        let reference = inner.find_reference(&format!("refs/tags/{}", name))?;
+        let target = match reference.peel_to_tag() {
+            Ok(tag) => (tag.target_id(), tag.message().map(String::from)),
+            Err(_) => {
+                let oid = reference
+                    .target()
+                    .ok_or_else(|| anyhow!("tag '{}' is not a direct reference", name))?;
+                (oid, None)
+            }
+        };
+        Ok(target)
+    }
+    pub fn tag_delete(&self, name: &str) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner.tag_delete(name)?)
+    }
+    /// Delete a tag on a remote by pushing an empty source to it
+    pub fn push_tag_deletion(&self, remote_name: &str, name: &str) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        push_refspecs(&inner, remote_name, &[format!(":refs/tags/{}", name)])
+    }
+    /// Push the current branch to a remote
+    pub fn push(&self, remote_name: &str) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let head = inner.head()?;
+        if !head.is_branch() {
+            return Err(anyhow!("HEAD is detached"));
+        }
+        let local_name = head
+            .name()
+            .ok_or_else(|| anyhow!("local name is not valid UTF-8"))?;
+        push_refspecs(&inner, remote_name, &[format!("{0}:{0}", local_name)])
+    }
+    /// Push a local branch to a remote, overwriting a diverged remote branch if `force`, and
+    /// make it track the pushed branch if `set_upstream`
+    pub fn push_branch(
+        &self,
+        branch: &str,
+        remote: &str,
+        set_upstream: bool,
+        force: bool,
+    ) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let mut local_branch = inner.find_branch(branch, git2::BranchType::Local)?;
+        let refspec = format!(
+            "{}refs/heads/{1}:refs/heads/{1}",
+            if force { "+" } else { "" },
+            branch
+        );
+        push_refspecs(&inner, remote, &[refspec])?;
+        if set_upstream {
+            local_branch.set_upstream(Some(&format!("{}/{}", remote, branch)))?;
+        }
+        Ok(())
+    }
+    /// Push the local tags to a remote, only those missing on it if `new_only`, returning the
+    /// names of the pushed tags
+    pub fn push_all_tags(&self, remote_name: &str, new_only: bool) -> Result<Vec<String>> {
+        let inner = self.inner.lock().unwrap();
+        let mut names = inner
+            .tag_names(None)?
+            .iter()
+            .flatten()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        if new_only {
+            let remote_refs = remote_ref_names(&inner, remote_name)?;
+            names.retain(|name| !remote_refs.contains(&format!("refs/tags/{}", name)));
+        }
+        if names.is_empty() {
+            return Ok(names);
+        }
+        let refspecs = names
+            .iter()
+            .map(|name| format!("+refs/tags/{0}:refs/tags/{0}", name))
+            .collect::<Vec<_>>();
+        push_refspecs(&inner, remote_name, &refspecs)?;
+        Ok(names)
+    }
+    /// Get the URL a remote pushes to, if it differs from its fetch URL
+    pub fn push_url(&self, remote_name: &str) -> Result<Option<String>> {
+        let inner = self.inner.lock().unwrap();
+        let remote = inner.find_remote(remote_name)?;
+        let push_url = remote.pushurl().map(String::from);
+        Ok(push_url)
+    }
+    pub fn set_push_url(&self, remote_name: &str, url: &str) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        inner.find_remote(remote_name)?;
+        Ok(inner.remote_set_pushurl(remote_name, Some(url))?)
+    }
+    /// Check that `url` can be pushed to by listing its references
+    pub fn verify_push_url(&self, url: &str) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let mut remote = inner.remote_anonymous(url)?;
+        let connection =
+            remote.connect_auth(git2::Direction::Push, Some(remote_callbacks()), None)?;
+        connection.list()?;
+        Ok(())
+    }
+    /// Check which remotes can be connected to within `timeout`, all of them being checked at once
+    pub fn remotes_with_connectivity(&self, timeout: Duration) -> Vec<(String, bool)> {
+        let (path, names) = {
+            let inner = self.inner.lock().unwrap();
+            let names = match inner.remotes() {
+                Ok(remotes) => remotes
+                    .iter()
+                    .flatten()
+                    .map(String::from)
+                    .collect::<Vec<_>>(),
+                Err(_) => Vec::new(),
+            };
+            (inner.path().to_owned(), names)
+        };
+        // Each check opens the repository again so that one which times out does not hold the lock
+        let receivers = names
+            .into_iter()
+            .map(|name| {
+                let (tx, rx) = mpsc::channel();
+                let path = path.clone();
+                let remote_name = name.clone();
+                thread::spawn(move || {
+                    let _ = tx.send(remote_reachable(&path, &remote_name));
+                });
+                (name, rx)
+            })
+            .collect::<Vec<_>>();
+        let deadline = Instant::now() + timeout;
+        receivers
+            .into_iter()
+            .map(|(name, rx)| {
+                let reachable = rx
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    .unwrap_or(false);
+                (name, reachable)
+            })
+            .collect()
+    }
+    /// Find the problems preventing the repository from being fetched, checking whether the
+    /// upstream remote can be connected to within `timeout`
+    pub fn problems(&self, timeout: Duration) -> Vec<Problem> {
+        let (path, remote_name) = {
+            let inner = self.inner.lock().unwrap();
+            let head = match inner.head() {
+                Ok(head) => head,
+                Err(e) if e.code() == git2::ErrorCode::UnbornBranch => {
+                    return vec![Problem::UnbornHead]
+                }
+                Err(e) => return vec![Problem::Other(e.message().to_string())],
+            };
+            if !head.is_branch() {
+                return vec![Problem::DetachedHead];
+            }
+            let remote_name = match head
+                .name()
+                .and_then(|name| inner.branch_upstream_remote(name).ok())
+                .and_then(|remote_name| remote_name.as_str().map(String::from))
+            {
+                Some(remote_name) => remote_name,
+                None => return vec![Problem::NoUpstream],
+            };
+            let url = inner
+                .find_remote(&remote_name)
+                .ok()
+                .and_then(|remote| remote.url().map(String::from))
+                .unwrap_or_default();
+            // Other credentials than the SSH key are not supported
+            let is_ssh = url.starts_with("ssh://") || (url.contains('@') && !url.contains("://"));
+            if is_ssh && !ssh_key_path().is_file() {
+                return vec![Problem::MissingSshKey];
+            }
+            (inner.path().to_owned(), remote_name)
+        };
+        let (tx, rx) = mpsc::channel();
+        let name = remote_name.clone();
+        thread::spawn(move || {
+            let _ = tx.send(remote_reachable(&path, &name));
+        });
+        if rx.recv_timeout(timeout).unwrap_or(false) {
+            Vec::new()
+        } else {
+            vec![Problem::UnreachableRemote(remote_name)]
+        }
+    }
+    /// Get the URL of the remote tracked by the current branch, or of `origin`
+    pub fn fetch_url(&self) -> Option<String> {
+        let remote_name = self
+            .upstream_remote()
+            .unwrap_or_else(|| "origin".to_string());
+        let inner = self.inner.lock().unwrap();
+        let remote = inner.find_remote(&remote_name).ok()?;
+        remote.url().map(String::from)
+    }
+    /// Get the URL of the web page of the remote tracked by the current branch, or of `origin`
+    pub fn web_url(&self) -> Result<String> {
+        let url = self
+            .fetch_url()
+            .ok_or_else(|| anyhow!("no remote to open"))?;
+        web_url(&url).ok_or_else(|| anyhow!("remote URL '{}' has no web page", url))
+    }
+    /// Get the name of the remote tracked by the current branch
+    pub fn upstream_remote(&self) -> Option<String> {
+        let inner = self.inner.lock().unwrap();
+        let head = inner.head().ok()?;
+        let remote_name = inner.branch_upstream_remote(head.name()?).ok()?;
+        remote_name.as_str().map(String::from)
+    }
+    /// Switch to an existing local branch
+    pub fn checkout_branch(&self, name: &str) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let branch = inner.find_branch(name, git2::BranchType::Local)?;
+        let reference = branch.into_reference();
+        checkout_reference(&inner, &reference)
+    }
+    /// Switch to a local branch, or create it from the branch of the same name of a remote, origin
+    /// being preferred, and track it
+    pub fn switch_branch(&self, name: &str) -> Result<BranchSwitch> {
+        let inner = self.inner.lock().unwrap();
+        if let Ok(branch) = inner.find_branch(name, git2::BranchType::Local) {
+            if branch.is_head() {
+                return Ok(BranchSwitch::Unchanged);
+            }
+            checkout_reference(&inner, &branch.into_reference())?;
+            return Ok(BranchSwitch::Switched);
+        }
+        let mut remotes = inner
+            .remotes()?
+            .iter()
+            .flatten()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        remotes.sort_by_key(|remote| remote != "origin");
+        let remote_branch = remotes.iter().find_map(|remote| {
+            let remote_name = format!("{}/{}", remote, name);
+            inner
+                .find_branch(&remote_name, git2::BranchType::Remote)
+                .ok()
+                .map(|branch| (remote_name, branch))
+        });
+        let (remote_name, remote_branch) = match remote_branch {
+            Some(remote_branch) => remote_branch,
+            None => return Ok(BranchSwitch::Missing),
+        };
+        let commit = remote_branch.get().peel_to_commit()?;
+        let mut branch = inner.branch(name, &commit, false)?;
+        branch.set_upstream(Some(&remote_name))?;
+        if let Err(e) = checkout_reference(&inner, branch.get()) {
+            // Do not leave a branch behind that was never switched to
+            branch.delete()?;
+            return Err(e);
+        }
+        Ok(BranchSwitch::Created(remote_name))
+    }
+    /// Rename a local branch, overwriting an existing branch if `force`
+    pub fn branch_rename(&self, old_name: &str, new_name: &str, force: bool) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let mut branch = inner.find_branch(old_name, git2::BranchType::Local)?;
+        let was_head = branch.is_head();
+        let upstream = branch
+            .upstream()
+            .ok()
+            .and_then(|upstream| upstream.name().ok().flatten().map(String::from));
+        let mut branch = branch.rename(new_name, force)?;
+        if was_head {
+            inner.set_head(&format!("refs/heads/{}", new_name))?;
+        }
+        // Keep tracking the same remote branch
+        if let Some(upstream) = upstream {
+            if branch.upstream().is_err() {
+                branch.set_upstream(Some(&upstream))?;
+            }
+        }
+        Ok(())
+    }
+    /// Get the local branches whose commits are all reachable from `into`, except `into` itself
+    pub fn list_merged_branches(&self, into: &str) -> Vec<String> {
+        let inner = self.inner.lock().unwrap();
+        let into_oid = match inner.revparse_single(into).and_then(|o| o.peel_to_commit()) {
+            Ok(commit) => commit.id(),
+            Err(_) => return Vec::new(),
+        };
+        // Branch `into` refers to, e.g. the current one for HEAD
+        let into_branch = inner
+            .resolve_reference_from_short_name(into)
+            .and_then(|reference| reference.resolve())
+            .ok()
+            .and_then(|reference| reference.name().map(String::from));
+        let branches = match inner.branches(Some(git2::BranchType::Local)) {
+            Ok(branches) => branches,
+            Err(_) => return Vec::new(),
+        };
+        branches
+            .flatten()
+            .filter_map(|(branch, _)| {
+                let name = branch.name().ok()??.to_string();
+                let oid = branch.get().target()?;
+                let (ahead, _) = inner.graph_ahead_behind(oid, into_oid).ok()?;
+                if ahead == 0 && branch.get().name() != into_branch.as_deref() {
+                    Some(name)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+    pub fn branch_delete(&self, name: &str) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let mut branch = inner.find_branch(name, git2::BranchType::Local)?;
+        Ok(branch.delete()?)
+    }
+    /// Create a branch at `start_point` and switch to it, overwriting an existing branch if `force`
+    pub fn checkout_create_branch(&self, name: &str, start_point: &str, force: bool) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let commit = inner.revparse_single(start_point)?.peel_to_commit()?;
+        let branch = inner.branch(name, &commit, force)?;
+        let reference = branch.into_reference();
+        checkout_reference(&inner, &reference)
+    }
+    /// Switch to a local branch fast-forwarded to `start_point`, creating it there if it does not
+    /// exist, returning the number of new commits
+    pub fn checkout_fast_forward(&self, name: &str, start_point: &str) -> Result<usize> {
+        let inner = self.inner.lock().unwrap();
+        let target = inner.revparse_single(start_point)?.peel_to_commit()?;
+        let branch = match inner.find_branch(name, git2::BranchType::Local) {
+            Ok(branch) => branch,
+            Err(_) => {
+                let mut branch = inner.branch(name, &target, false)?;
+                if let Err(e) = checkout_reference(&inner, branch.get()) {
+                    // Do not leave a branch behind that was never switched to
+                    branch.delete()?;
+                    return Err(e);
+                }
+                return Ok(0);
+            }
+        };
+        let is_head = branch.is_head();
+        let reference = branch.into_reference();
+        let (ahead, behind) =
+            fast_forward_reference(&inner, &reference, target.id(), "glim: fast-forward")?;
+        if ahead > 0 {
+            return Err(anyhow!(
+                "cannot fast-forward, '{}' diverged from '{}'",
+                name,
+                start_point
+            ));
+        }
+        if !is_head {
+            // Switch to the fast-forwarded branch rather than its previous target
+            let reference = inner.find_branch(name, git2::BranchType::Local)?;
+            checkout_reference(&inner, reference.get())?;
+        }
+        Ok(behind)
+    }
+    /// Count the references stored in the `packed-refs` file
+    pub fn packed_ref_count(&self) -> usize {
+        let inner = self.inner.lock().unwrap();
+        std::fs::read_to_string(inner.path().join("packed-refs"))
+            .map(|content| {
+                content
+                    .lines()
+                    // Skip comments and peeled targets of annotated tags
+                    .filter(|line| {
+                        !line.is_empty() && !line.starts_with('#') && !line.starts_with('^')
+                    })
+                    .count()
+            })
+            .unwrap_or(0)
+    }
+    /// Get the names of the direct references whose target object does not exist
+    pub fn unreachable_refs(&self) -> Vec<String> {
+        let inner = self.inner.lock().unwrap();
+        let references = match inner.references() {
+            Ok(references) => references,
+            Err(_) => return Vec::new(),
+        };
+        references
+            .flatten()
+            .filter(|reference| match reference.target() {
+                Some(oid) => inner.find_object(oid, None).is_err(),
+                None => false,
+            })
+            .filter_map(|reference| reference.name().map(String::from))
+            .collect()
+    }
+    /// Look for references whose target object does not exist, failing if there are any
+    ///
+    /// They are only looked for the first time, later calls reuse the result.
+    pub fn check_refs(&mut self) -> Result<()> {
+        if self.broken_refs.is_none() {
+            self.broken_refs = Some(self.unreachable_refs());
+        }
+        match self.broken_refs.as_deref().unwrap_or_default() {
+            [] => Ok(()),
+            broken_refs => Err(BrokenReferences(broken_refs.to_vec()).into()),
+        }
+    }
+    /// Get the references found by `check_refs` whose target object does not exist
+    pub fn broken_refs(&self) -> Option<&[String]> {
+        self.broken_refs.as_deref()
+    }
+    pub fn ref_count(&self) -> Result<usize> {
+        let inner = self.inner.lock().unwrap();
+        let count = inner.references()?.count();
+        Ok(count)
+    }
+    /// Resolve a revision (e.g. a short OID or a branch) to a commit
+    pub fn resolve_commit(&self, spec: &str) -> Result<git2::Oid> {
+        let inner = self.inner.lock().unwrap();
+        let commit = inner.revparse_single(spec)?.peel_to_commit()?;
+        Ok(commit.id())
+    }
+    /// Whether the object database has a commit with id `oid`, without parsing it
+    pub fn commit_exists(&self, oid: git2::Oid) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .odb()
+            .and_then(|odb| odb.read_header(oid))
+            .is_ok_and(|(_, kind)| kind == git2::ObjectType::Commit)
+    }
+    /// Get the `user.name` and `user.email` commits are authored with
+    pub fn current_user_identity(&self) -> Option<(String, String)> {
+        let inner = self.inner.lock().unwrap();
+        let config = inner.config().ok()?;
+        let name = config.get_string("user.name").ok()?;
+        let email = config.get_string("user.email").ok()?;
+        Some((name, email))
+    }
+    /// Whether commits would be authored with another email than `expected`
+    pub fn detect_email_mismatch(&self, expected: &str) -> bool {
+        match self.current_user_identity() {
+            Some((_, email)) => email != expected,
+            None => true,
+        }
+    }
+    /// Set `user.email` in the config of the repository
+    pub fn set_email(&self, email: &str) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let mut config = inner.config()?.open_level(git2::ConfigLevel::Local)?;
+        Ok(config.set_str("user.email", email)?)
+    }
+    /// Remove `key` from the config of the repository, with all its values if `all`
+    pub fn config_unset(&self, key: &str, all: bool) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let mut config = inner.config()?.open_level(git2::ConfigLevel::Local)?;
+        if all {
+            config.remove_multivar(key, ".*")?;
+        } else {
+            config.remove(key)?;
+        }
+        Ok(())
+    }
+    /// List the notes of `refs/notes/commits`, none if it does not exist
+    pub fn notes_list(&self) -> Result<Vec<NoteEntry>> {
+        let inner = self.inner.lock().unwrap();
+        let notes = match inner.notes(Some(NOTES_REF)) {
+            Ok(notes) => notes,
+            Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut entries = Vec::new();
+        for note in notes {
+            let (note_oid, annotated_oid) = note?;
+            let note = inner.find_note(Some(NOTES_REF), annotated_oid)?;
+            entries.push(NoteEntry {
+                annotated_oid,
+                note_oid,
+                content: note.message().unwrap_or_default().to_string(),
+            });
+        }
+        Ok(entries)
+    }
+    /// Remove the note attached to `oid` in `refs/notes/commits`
+    pub fn note_remove(&self, oid: git2::Oid) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let signature = inner.signature()?;
+        inner
+            .note_delete(oid, Some(NOTES_REF), &signature, &signature)
+            .map_err(|_| anyhow!("{} has no note", oid))
+    }
+    /// Delete entries of the reflog of a reference, all of them if `entries` is `None`, returning
+    /// the number of deleted entries
+    pub fn ref_log_delete(&self, reference: &str, entries: Option<&[usize]>) -> Result<usize> {
+        let inner = self.inner.lock().unwrap();
+        let mut reflog = inner.reflog(reference)?;
+        let entries = match entries {
+            Some(entries) => entries.iter().cloned().collect::<BTreeSet<_>>(),
+            None => {
+                let count = reflog.len();
+                inner.reflog_delete(reference)?;
+                return Ok(count);
+            }
+        };
+        if let Some(index) = entries.iter().find(|index| **index >= reflog.len()) {
+            return Err(anyhow!("{}@{{{}}} does not exist", reference, index));
+        }
+        // Drop from the oldest entries so that the indices of the others do not shift
+        for index in entries.iter().rev() {
+            reflog.remove(*index, true)?;
+        }
+        reflog.write()?;
+        Ok(entries.len())
+    }
+    /// Get the key used to sign commits from the git config
+    pub fn config_signing_key(&self) -> Option<String> {
+        let inner = self.inner.lock().unwrap();
+        let config = inner.config().ok()?;
+        config.get_string("user.signingKey").ok()
+    }
+    /// Get the unsigned commits among the last `count` first-parent commits from HEAD
+    pub fn unsigned_commits(&self, count: usize) -> Result<Vec<git2::Oid>> {
+        let inner = self.inner.lock().unwrap();
+        let mut revwalk = inner.revwalk()?;
+        revwalk.push_head()?;
+        revwalk.simplify_first_parent()?;
+        let mut unsigned = Vec::new();
+        for oid in revwalk.take(count) {
+            let oid = oid?;
+            if inner.extract_signature(&oid, None).is_err() {
+                unsigned.push(oid);
+            }
+        }
+        Ok(unsigned)
+    }
+    pub fn sign_commit(&self, oid: git2::Oid) -> Result<()> {
+        self.sign_commits(&[oid]).map(|_| ())
+    }
+    /// Sign commits of the first-parent history of HEAD, returning the new HEAD
+    ///
+    /// Signing changes the OID of a commit, so every commit following the oldest
+    /// signed one is rewritten, and those which were already signed are signed again. This
+    /// fails if one of them was committed by someone else, whose signature would be replaced.
+    pub fn sign_commits(&self, oids: &[git2::Oid]) -> Result<git2::Oid> {
+        let signing_key = self.config_signing_key();
+        let inner = self.inner.lock().unwrap();
+        let head = inner.head()?;
+        let head_oid = head
+            .target()
+            .ok_or_else(|| anyhow!("HEAD is not a direct reference"))?;
+
+        // Collect the first-parent chain from HEAD down to the oldest commit to sign
+        let to_sign = oids.iter().cloned().collect::<HashSet<_>>();
+        let mut remaining = to_sign.clone();
+        let mut chain = Vec::new();
+        let mut commit = inner.find_commit(head_oid)?;
+        loop {
+            remaining.remove(&commit.id());
+            chain.push(commit.id());
+            if remaining.is_empty() {
+                break;
+            }
+            commit = commit
+                .parent(0)
+                .map_err(|_| anyhow!("commits are not in the first-parent history of HEAD"))?;
+        }
+
+        // Only signatures of the user can be made again
+        let user = config_signature(&inner)?;
+        for oid in &chain {
+            let commit = inner.find_commit(*oid)?;
+            if inner.extract_signature(oid, None).is_ok()
+                && commit.committer().email_bytes() != user.email_bytes()
+            {
+                return Err(anyhow!(
+                    "{} is signed by {}, its signature would be lost",
+                    oid,
+                    commit.committer()
+                ));
+            }
+        }
+
+        // Rewrite commits starting from the oldest one
+        let mut rewritten = HashMap::new();
+        for oid in chain.into_iter().rev() {
+            let commit = inner.find_commit(oid)?;
+            let parents = commit
+                .parent_ids()
+                .map(|parent_id| {
+                    inner.find_commit(*rewritten.get(&parent_id).unwrap_or(&parent_id))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let parents = parents.iter().collect::<Vec<_>>();
+            let buffer = inner.commit_create_buffer(
+                &commit.author(),
+                &commit.committer(),
+                commit
+                    .message_raw()
+                    .ok_or_else(|| anyhow!("message of {} is not valid UTF-8", oid))?,
+                &commit.tree()?,
+                &parents,
+            )?;
+            let content = buffer
+                .as_str()
+                .ok_or_else(|| anyhow!("commit {} is not valid UTF-8", oid))?;
+            let new_oid = if to_sign.contains(&oid) || inner.extract_signature(&oid, None).is_ok() {
+                let signature = gpg_sign(signing_key.as_deref(), content.as_bytes())?;
+                inner.commit_signed(content, &signature, None)?
+            } else {
+                inner
+                    .odb()?
+                    .write(git2::ObjectType::Commit, content.as_bytes())?
+            };
+            rewritten.insert(oid, new_oid);
+        }
+
+        // Move HEAD to the rewritten commit
+        let new_head = rewritten[&head_oid];
+        match head.name() {
+            Some(name) if head.is_branch() => {
+                inner.reference(name, new_head, true, "glim: sign commits")?;
+            }
+            _ => inner.set_head_detached(new_head)?,
+        }
+        Ok(new_head)
+    }
+    /// Start a bisection between a good and a bad commit and check out the first commit to test
+    pub fn bisect_start(&self, good: git2::Oid, bad: git2::Oid) -> Result<BisectStep> {
+        {
+            let inner = self.inner.lock().unwrap();
+            let git_dir = inner.path();
+            if git_dir.join("BISECT_START").exists() {
+                return Err(anyhow!("a bisection is already in progress"));
+            }
+            // Remember where to go back on reset
+            let head = inner.head()?;
+            let start = match head.shorthand() {
+                Some(branch) if head.is_branch() => branch.to_string(),
+                _ => head
+                    .target()
+                    .ok_or_else(|| anyhow!("HEAD is not a direct reference"))?
+                    .to_string(),
+            };
+            std::fs::write(git_dir.join("BISECT_START"), format!("{}\n", start))?;
+            std::fs::write(git_dir.join("BISECT_TERMS"), "bad\ngood\n")?;
+            std::fs::write(git_dir.join("BISECT_NAMES"), "\n")?;
+            bisect_mark(&inner, "bad", bad)?;
+            bisect_mark(&inner, "good", good)?;
+            bisect_log(&inner, &format!("git bisect start '{}' '{}'", bad, good))?;
+        }
+        self.bisect_next()
+    }
+    pub fn bisect_good(&self) -> Result<BisectStep> {
+        self.bisect_mark_head("good")
+    }
+    pub fn bisect_bad(&self) -> Result<BisectStep> {
+        self.bisect_mark_head("bad")
+    }
+    pub fn bisect_skip(&self) -> Result<BisectStep> {
+        self.bisect_mark_head("skip")
+    }
+    fn bisect_mark_head(&self, term: &str) -> Result<BisectStep> {
+        {
+            let inner = self.inner.lock().unwrap();
+            if !inner.path().join("BISECT_START").exists() {
+                return Err(anyhow!("no bisection in progress"));
+            }
+            let oid = inner.head()?.peel_to_commit()?.id();
+            bisect_mark(&inner, term, oid)?;
+            bisect_log(&inner, &format!("git bisect {} {}", term, oid))?;
+        }
+        self.bisect_next()
+    }
+    /// Check out the next commit to test according to the marks of `BISECT_LOG`
+    pub fn bisect_next(&self) -> Result<BisectStep> {
+        let inner = self.inner.lock().unwrap();
+        let log = std::fs::read_to_string(inner.path().join("BISECT_LOG"))
+            .map_err(|_| anyhow!("no bisection in progress"))?;
+
+        // Marks are logged as `# <term>: [<oid>] <summary>`
+        let mut bad = None;
+        let mut goods = Vec::new();
+        let mut skips = HashSet::new();
+        for line in log.lines() {
+            let mark = line
+                .strip_prefix("# ")
+                .and_then(|mark| mark.split_once(": ["))
+                .and_then(|(term, rest)| Some((term, rest.split_once(']')?.0)));
+            if let Some((term, oid)) = mark {
+                let oid = git2::Oid::from_str(oid)?;
+                match term {
+                    "bad" => bad = Some(oid),
+                    "good" => goods.push(oid),
+                    "skip" => {
+                        skips.insert(oid);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let bad = bad.ok_or_else(|| anyhow!("no bad commit marked"))?;
+
+        // Suspects are the commits reachable from the bad one but not from the good ones
+        let mut revwalk = inner.revwalk()?;
+        revwalk.set_sorting(git2::Sort::TOPOLOGICAL)?;
+        revwalk.push(bad)?;
+        for good in goods {
+            revwalk.hide(good)?;
+        }
+        let candidates = revwalk.collect::<Result<Vec<_>, _>>()?;
+        let suspects = candidates
+            .iter()
+            .filter(|oid| **oid != bad && !skips.contains(oid))
+            .collect::<Vec<_>>();
+        if candidates.len() <= 1 {
+            let summary = inner
+                .find_commit(bad)?
+                .summary()
+                .unwrap_or_default()
+                .to_string();
+            bisect_log(
+                &inner,
+                &format!("# first bad commit: [{}] {}", bad, summary),
+            )?;
+            return Ok(BisectStep::Found(bad));
+        }
+        if suspects.is_empty() {
+            return Err(anyhow!("only skipped commits are left to test"));
+        }
+
+        // Test the commit in the middle of the suspects
+        let next = *suspects[suspects.len() / 2];
+        std::fs::write(
+            inner.path().join("BISECT_EXPECTED_REV"),
+            format!("{}\n", next),
+        )?;
+        let commit = inner.find_commit(next)?;
+        inner.checkout_tree(commit.as_object(), None)?;
+        inner.set_head_detached(next)?;
+        Ok(BisectStep::Test(next, suspects.len()))
+    }
+    /// End a bisection and go back to where it was started
+    pub fn bisect_reset(&self) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let git_dir = inner.path().to_owned();
+        let start = std::fs::read_to_string(git_dir.join("BISECT_START"))
+            .map_err(|_| anyhow!("no bisection in progress"))?;
+        let start = start.trim();
+        match git2::Oid::from_str(start) {
+            Ok(oid) if start.len() == 40 => {
+                let commit = inner.find_commit(oid)?;
+                inner.checkout_tree(commit.as_object(), None)?;
+                inner.set_head_detached(oid)?;
+            }
+            _ => {
+                let reference = inner.find_reference(&format!("refs/heads/{}", start))?;
+                checkout_reference(&inner, &reference)?;
+            }
+        }
+
+        // Remove bisection state
+        for name in &[
+            "BISECT_START",
+            "BISECT_TERMS",
+            "BISECT_NAMES",
+            "BISECT_LOG",
+            "BISECT_EXPECTED_REV",
+            "BISECT_ANCESTORS_OK",
+        ] {
+            let path = git_dir.join(name);
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        for reference in inner.references_glob("refs/bisect/*")? {
+            reference?.delete()?;
+        }
+        Ok(())
+    }
+    /// Read the graft definitions of `info/grafts`, redefining the parents of commits
+    pub fn grafts(&self) -> Vec<(git2::Oid, Vec<git2::Oid>)> {
+        let inner = self.inner.lock().unwrap();
+        read_grafts(&inner)
+    }
+    pub fn add_graft(&self, child: git2::Oid, parents: &[git2::Oid]) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let mut grafts = read_grafts(&inner);
+        grafts.retain(|(oid, _)| *oid != child);
+        grafts.push((child, parents.to_vec()));
+        write_grafts(&inner, &grafts)
+    }
+    pub fn remove_graft(&self, child: git2::Oid) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let mut grafts = read_grafts(&inner);
+        let len = grafts.len();
+        grafts.retain(|(oid, _)| *oid != child);
+        if grafts.len() == len {
+            return Err(anyhow!("no graft for {}", child));
+        }
+        write_grafts(&inner, &grafts)
+    }
+    /// Wrap the existing hooks in a shim logging their failures to `glim_hook_log.json`,
+    /// returning the names of the newly wrapped hooks
+    pub fn install_logging_hooks(&self) -> Result<Vec<String>> {
+        let inner = self.inner.lock().unwrap();
+        let hooks_dir = inner.path().join("hooks");
+        let mut installed = Vec::new();
+        for hook in HOOKS {
+            let path = hooks_dir.join(hook);
+            let original = hooks_dir.join(format!("{}.glim-orig", hook));
+            // Skip missing hooks and those already wrapped
+            if !path.is_file() || original.exists() {
+                continue;
+            }
+            std::fs::rename(&path, &original)?;
+            std::fs::write(&path, HOOK_SHIM.replace("{hook}", hook))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+            }
+            installed.push(hook.to_string());
+        }
+        Ok(installed)
+    }
+    /// Read the hook failures logged by the hooks installed with `install_logging_hooks`
+    pub fn detect_hook_failures(&self) -> Vec<HookFailure> {
+        let inner = self.inner.lock().unwrap();
+        std::fs::read_to_string(inner.path().join("glim_hook_log.json"))
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+    /// Take a snapshot of the state of the repository, counting 0 for what cannot be read
+    pub fn diagnose(&self) -> DiagnosticReport {
+        let mut inner = self.inner.lock().unwrap();
+        let objects_dir = inner.path().join("objects");
+        // Loose objects are stored in directories named after the first two hex digits of their OID
+        let loose_object_count = std::fs::read_dir(&objects_dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter(|entry| {
+                        let name = entry.file_name();
+                        let name = name.to_string_lossy();
+                        name.len() == 2 && name.chars().all(|c| c.is_ascii_hexdigit())
+                    })
+                    .filter_map(|entry| std::fs::read_dir(entry.path()).ok())
+                    .map(|objects| objects.count())
+                    .sum()
+            })
+            .unwrap_or(0);
+        let pack_count = std::fs::read_dir(objects_dir.join("pack"))
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "pack"))
+                    .count()
+            })
+            .unwrap_or(0);
+        let has_upstream = inner
+            .head()
+            .ok()
+            .and_then(|head| head.name().map(String::from))
+            .is_some_and(|name| inner.branch_upstream_name(&name).is_ok());
+        let conflict_count = inner
+            .index()
+            .and_then(|index| index.conflicts().map(|conflicts| conflicts.count()))
+            .unwrap_or(0);
+        let pending_operation = match inner.state() {
+            git2::RepositoryState::Clean => None,
+            state => Some(state),
+        };
+        let mut report = DiagnosticReport {
+            is_bare: inner.is_bare(),
+            is_shallow: inner.is_shallow(),
+            is_empty: inner.is_empty().unwrap_or(false),
+            has_upstream,
+            index_locked: inner.path().join("index.lock").exists(),
+            loose_object_count,
+            pack_count,
+            conflict_count,
+            stash_count: 0,
+            note_count: inner
+                .notes(Some(NOTES_REF))
+                .map_or(0, |notes| notes.count()),
+            worktree_count: inner.worktrees().map_or(0, |worktrees| worktrees.len()),
+            submodule_count: inner.submodules().map_or(0, |submodules| submodules.len()),
+            pending_operation,
+        };
+        let _ = inner.stash_foreach(|_, _, _| {
+            report.stash_count += 1;
+            true
+        });
+        report
+    }
+    pub fn state(&self) -> git2::RepositoryState {
+        let inner = self.inner.lock().unwrap();
+        inner.state()
+    }
+    pub fn merge_abort(&self) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        reset_to_head(&inner)?;
+        Ok(inner.cleanup_state()?)
+    }
+    pub fn rebase_abort(&self) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        // libgit2 cannot open every rebase started by git, so restore the original head by hand
+        let rebase_dir = ["rebase-merge", "rebase-apply"]
+            .iter()
+            .map(|dir| inner.path().join(dir))
+            .find(|dir| dir.is_dir())
+            .ok_or_else(|| anyhow!("no rebase in progress"))?;
+        let orig_head =
+            git2::Oid::from_str(std::fs::read_to_string(rebase_dir.join("orig-head"))?.trim())?;
+        let head_name = std::fs::read_to_string(rebase_dir.join("head-name"))?;
+        let head_name = head_name.trim();
+        if head_name == "detached HEAD" {
+            inner.set_head_detached(orig_head)?;
+        } else {
+            inner.reference(head_name, orig_head, true, "rebase: aborting")?;
+            inner.set_head(head_name)?;
+        }
+        // Resetting already cleans up the rebase state when libgit2 recognizes it
+        reset_to_head(&inner)?;
+        if rebase_dir.is_dir() {
+            std::fs::remove_dir_all(rebase_dir)?;
+        }
+        Ok(())
+    }
+    pub fn cherry_pick_abort(&self) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        reset_to_head(&inner)?;
+        Ok(inner.cleanup_state()?)
+    }
+    /// List the sorted paths of the index containing `pattern`, or matching it as a glob if `glob`
+    pub fn list_tracked_paths_matching(&self, pattern: &str, glob: bool) -> Result<Vec<String>> {
+        let inner = self.inner.lock().unwrap();
+        let glob_pattern = if glob {
+            Some(glob::Pattern::new(pattern)?)
+        } else {
+            None
+        };
+        let mut paths = inner
+            .index()?
+            .iter()
+            .filter_map(|entry| String::from_utf8(entry.path).ok())
+            .filter(|path| match &glob_pattern {
+                Some(glob_pattern) => glob_pattern.matches(path),
+                None => path.contains(pattern),
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+        Ok(paths)
+    }
+    /// Create a patch in unified diff format of the changes between two references, with the
+    /// content of binary files if `binary`
+    pub fn create_patch(&self, from: &str, to: &str, binary: bool) -> Result<Vec<u8>> {
+        let inner = self.inner.lock().unwrap();
+        let from = tree_at(&inner, from)?;
+        let to = tree_at(&inner, to)?;
+        let mut options = git2::DiffOptions::new();
+        options.force_binary(binary).show_binary(binary);
+        let diff = inner.diff_tree_to_tree(Some(&from), Some(&to), Some(&mut options))?;
+        diff_to_patch(&diff)
+    }
+    /// Get the statistics of the changes of the stash at `index` (0 being the latest one)
+    pub fn stash_show(&self, index: usize) -> Result<DiffStat> {
+        let mut inner = self.inner.lock().unwrap();
+        let oid = stash_id(&mut inner, index)?;
+        let stats = stash_diff(&inner, oid)?.stats()?;
+        Ok(DiffStat {
+            files_changed: stats.files_changed(),
+            insertions: stats.insertions(),
+            deletions: stats.deletions(),
+        })
+    }
+    /// Get the changes of the stash at `index` (0 being the latest one) as a patch
+    pub fn stash_patch(&self, index: usize) -> Result<Vec<u8>> {
+        let mut inner = self.inner.lock().unwrap();
+        let oid = stash_id(&mut inner, index)?;
+        let patch = diff_to_patch(&stash_diff(&inner, oid)?);
+        patch
+    }
+    /// Update an initialized submodule, and its nested submodules depth-first if `recursive`
+    pub fn submodule_update(&self, submodule_name: &str, recursive: bool) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let config = inner.config()?;
+        if config
+            .get_string(&format!("submodule.{}.url", submodule_name))
+            .is_err()
+        {
+            return Err(anyhow!(
+                "submodule '{}' is not initialized, run `git submodule init` first",
+                submodule_name
+            ));
+        }
+        update_submodule(&inner, submodule_name, false, recursive)
+    }
+    /// Update every submodule, returning the result of each one
+    pub fn update_all_submodules(&self, recursive: bool) -> Vec<(String, Result<()>)> {
+        let names = {
+            let inner = self.inner.lock().unwrap();
+            let names = match inner.submodules() {
+                Ok(submodules) => submodules
+                    .iter()
+                    .filter_map(|submodule| submodule.name().map(String::from))
+                    .collect::<Vec<_>>(),
+                Err(e) => return vec![(String::new(), Err(e.into()))],
+            };
+            names
+        };
+        names
+            .into_iter()
+            .map(|name| {
+                let result = self.submodule_update(&name, recursive);
+                (name, result)
+            })
+            .collect()
+    }
+    /// Count the inserted and deleted lines between the trees of two references
+    pub fn total_additions_deletions(&self, from: &str, to: &str) -> Result<(usize, usize)> {
+        let inner = self.inner.lock().unwrap();
+        let from = tree_at(&inner, from)?;
+        let to = tree_at(&inner, to)?;
+        let stats = inner
+            .diff_tree_to_tree(Some(&from), Some(&to), None)?
+            .stats()?;
+        Ok((stats.insertions(), stats.deletions()))
+    }
+    /// Get the last `limit` commits touching `path` following first parents from HEAD
+    pub fn file_history(&self, path: &str, limit: usize) -> Result<Vec<CommitInfo>> {
+        let inner = self.inner.lock().unwrap();
+        let mut revwalk = inner.revwalk()?;
+        revwalk.push_head()?;
+        revwalk.simplify_first_parent()?;
+        let mut diff_options = git2::DiffOptions::new();
+        diff_options.pathspec(path);
+
+        let mut history = Vec::new();
+        for oid in revwalk {
+            if history.len() >= limit {
+                break;
+            }
+            let commit = inner.find_commit(oid?)?;
+            let parent_tree = match commit.parents().next() {
+                Some(parent) => Some(parent.tree()?),
+                None => None,
+            };
+            let diff = inner.diff_tree_to_tree(
+                parent_tree.as_ref(),
+                Some(&commit.tree()?),
+                Some(&mut diff_options),
+            )?;
+            if diff.deltas().len() > 0 {
+                history.push(CommitInfo::from(&commit));
+            }
+        }
+        Ok(history)
+    }
+    /// Get the last `limit` commits following first parents from HEAD
+    pub fn recent_commits(&self, limit: usize) -> Result<Vec<CommitInfo>> {
+        let inner = self.inner.lock().unwrap();
+        // An unborn HEAD has no commits
+        if matches!(inner.head(), Err(e) if e.code() == git2::ErrorCode::UnbornBranch) {
+            return Ok(Vec::new());
+        }
+        let mut revwalk = inner.revwalk()?;
+        revwalk.push_head()?;
+        revwalk.simplify_first_parent()?;
+        revwalk
+            .take(limit)
+            .map(|oid| Ok(CommitInfo::from(&inner.find_commit(oid?)?)))
+            .collect()
+    }
+    /// Get the messages of the stashes, the latest one first
+    pub fn stash_list(&self) -> Result<Vec<CommitInfo>> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut stashes = Vec::new();
+        inner.stash_foreach(|_, message, oid| {
+            stashes.push((*oid, message.to_string()));
+            true
+        })?;
+        stashes
+            .into_iter()
+            .map(|(oid, message)| {
+                let mut stash = CommitInfo::from(&inner.find_commit(oid)?);
+                stash.summary = message;
+                Ok(stash)
+            })
+            .collect()
+    }
+    /// Get the files which are staged, modified or untracked along with their status
+    pub fn changed_files(&self) -> Result<Vec<(git2::Status, String)>> {
+        let inner = self.inner.lock().unwrap();
+        let mut status_options = git2::StatusOptions::new();
+        status_options
+            .show(git2::StatusShow::IndexAndWorkdir)
+            .include_untracked(true)
+            .include_ignored(false);
+        let statuses = inner.statuses(Some(&mut status_options))?;
+        Ok(statuses
+            .iter()
+            .filter_map(|entry| Some((entry.status(), entry.path()?.to_string())))
+            .collect())
+    }
+    /// Get the commits among the last `RECENT_COMMIT_COUNT` first-parent commits from HEAD whose
+    /// added and modified files weigh more than `threshold_bytes`, along with their weight
+    pub fn detect_large_commits(&self, threshold_bytes: u64) -> Vec<(git2::Oid, u64)> {
+        let inner = self.inner.lock().unwrap();
+        let detect = || -> Result<Vec<(git2::Oid, u64)>> {
+            let odb = inner.odb()?;
+            let mut revwalk = inner.revwalk()?;
+            revwalk.push_head()?;
+            revwalk.simplify_first_parent()?;
+            let mut large_commits = Vec::new();
+            for oid in revwalk.take(RECENT_COMMIT_COUNT) {
+                let commit = inner.find_commit(oid?)?;
+                let parent_tree = match commit.parents().next() {
+                    Some(parent) => Some(parent.tree()?),
+                    None => None,
+                };
+                let diff =
+                    inner.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+                let mut size = 0;
+                diff.foreach(
+                    &mut |delta, _| {
+                        if let git2::Delta::Added | git2::Delta::Modified = delta.status() {
+                            let file = delta.new_file();
+                            // Sizes are not always filled in for diffs between trees
+                            size += match file.size() {
+                                0 => odb
+                                    .read_header(file.id())
+                                    .map_or(0, |(size, _)| size as u64),
+                                size => size,
+                            };
+                        }
+                        true
+                    },
+                    None,
+                    None,
+                    None,
+                )?;
+                if size > threshold_bytes {
+                    large_commits.push((commit.id(), size));
+                }
+            }
+            Ok(large_commits)
+        };
+        detect().unwrap_or_default()
+    }
+    /// Count the commits reachable from HEAD of the last `days` days per author email, most
+    /// active authors first
+    pub fn list_contributors_since(&self, days: u32) -> Vec<(String, usize)> {
+        let mailmap = self.read_mailmap();
+        let inner = self.inner.lock().unwrap();
+        let since = (chrono::Utc::now() - chrono::Duration::days(days as i64)).timestamp();
+        let list = || -> Result<Vec<(String, usize)>> {
+            let mut revwalk = inner.revwalk()?;
+            revwalk.push_head()?;
+            revwalk.set_sorting(git2::Sort::TIME)?;
+            let mut counts = HashMap::new();
+            for oid in revwalk {
+                let commit = inner.find_commit(oid?)?;
+                // Commits are sorted by committer date, newest first
+                if commit.time().seconds() < since {
+                    break;
+                }
+                let author = commit.author();
+                let (_, email) = normalize_author(
+                    &mailmap,
+                    author.name().unwrap_or_default(),
+                    author.email().unwrap_or_default(),
+                );
+                *counts.entry(email).or_insert(0) += 1;
+            }
+            let mut contributors = counts.into_iter().collect::<Vec<_>>();
+            contributors.sort_by(|(a_email, a_count), (b_email, b_count)| {
+                b_count.cmp(a_count).then_with(|| a_email.cmp(b_email))
+            });
+            Ok(contributors)
+        };
+        list().unwrap_or_default()
+    }
+    /// Parse the `.mailmap` file at the root of the working tree
+    pub fn read_mailmap(&self) -> Vec<MailmapEntry> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .workdir()
+            .and_then(|workdir| std::fs::read_to_string(workdir.join(".mailmap")).ok())
+            .map(|content| content.lines().filter_map(MailmapEntry::parse).collect())
+            .unwrap_or_default()
+    }
+    /// Count the objects reachable from the references, reusing the counts cached in
+    /// `glim_object_counts.json` as long as the references do not change
+    pub fn count_reachable_objects(&self) -> Result<ObjectCounts> {
+        if let Some(counts) = self.cached_object_counts() {
+            return Ok(counts);
+        }
+        let inner = self.inner.lock().unwrap();
+        let mut revwalk = inner.revwalk()?;
+        revwalk.push_glob("*")?;
+        let mut counts = ObjectCounts::default();
+        let mut trees = HashSet::new();
+        let mut blobs = HashSet::new();
+        for oid in revwalk {
+            let commit = inner.find_commit(oid?)?;
+            counts.commits += 1;
+            count_tree_objects(&inner, commit.tree_id(), &mut trees, &mut blobs)?;
+        }
+        counts.trees = trees.len();
+        counts.blobs = blobs.len();
+        inner.tag_foreach(|oid, _| {
+            // Lightweight tags are references to commits rather than tag objects
+            if inner.find_tag(oid).is_ok() {
+                counts.tags += 1;
+            }
+            true
+        })?;
+        let cache = ObjectCountsCache {
+            refs_hash: refs_hash(&inner)?,
+            counts,
+        };
+        std::fs::write(
+            inner.path().join("glim_object_counts.json"),
+            serde_json::to_string(&cache)?,
+        )?;
+        Ok(counts)
+    }
+    /// Get the counts of `count_reachable_objects` if they are cached and still valid
+    pub fn cached_object_counts(&self) -> Option<ObjectCounts> {
+        let inner = self.inner.lock().unwrap();
+        let content = std::fs::read_to_string(inner.path().join("glim_object_counts.json")).ok()?;
+        let cache: ObjectCountsCache = serde_json::from_str(&content).ok()?;
+        if cache.refs_hash == refs_hash(&inner).ok()? {
+            Some(cache.counts)
+        } else {
+            None
+        }
+    }
+    /// Get the paths of the files of the index whose content has a null byte, as binary files do
+    pub fn detect_binary_files_in_index(&self) -> Vec<String> {
+        let inner = self.inner.lock().unwrap();
+        let index = match inner.index() {
+            Ok(index) => index,
+            Err(_) => return Vec::new(),
+        };
+        index
+            .iter()
+            .filter_map(|entry| {
+                let blob = inner.find_blob(entry.id).ok()?;
+                if blob.content().contains(&0) {
+                    Some(String::from_utf8_lossy(&entry.path).into_owned())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+    /// Get the paths of the text files of the index with CRLF line endings, only checking those
+    /// whose attributes set `text=auto` or `eol=lf`
+    pub fn detect_non_lf_line_endings(&self) -> Vec<String> {
+        let inner = self.inner.lock().unwrap();
+        let index = match inner.index() {
+            Ok(index) => index,
+            Err(_) => return Vec::new(),
+        };
+        index
+            .iter()
+            .filter_map(|entry| {
+                let path = String::from_utf8(entry.path).ok()?;
+                let attr = |name| {
+                    inner
+                        .get_attr(Path::new(&path), name, git2::AttrCheckFlags::INDEX_ONLY)
+                        .ok()
+                        .map(git2::AttrValue::from_string)
+                };
+                let text_auto = matches!(attr("text"), Some(git2::AttrValue::String("auto")));
+                let eol_lf = matches!(attr("eol"), Some(git2::AttrValue::String("lf")));
+                if !text_auto && !eol_lf {
+                    return None;
+                }
+                let blob = inner.find_blob(entry.id).ok()?;
+                let content = blob.content();
+                if !blob.is_binary() && content.windows(2).any(|window| window == b"\r\n") {
+                    Some(path)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+    /// Sum the sizes of the blobs of the tree at `reference`, reusing the size cached in
+    /// `glim_tree_size.json` if the tree did not change
+    pub fn tree_size(&self, reference: &str) -> Result<u64> {
+        let inner = self.inner.lock().unwrap();
+        let tree = tree_at(&inner, reference)?;
+        let cache_path = inner.path().join("glim_tree_size.json");
+        let cache = std::fs::read_to_string(&cache_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<TreeSizeCache>(&content).ok());
+        if let Some(cache) = cache {
+            if cache.tree == tree.id().to_string() {
+                return Ok(cache.size);
+            }
+        }
+        // Reading headers gives the sizes without inflating the blobs
+        let odb = inner.odb()?;
+        let mut size = 0;
+        let mut result = Ok(());
+        tree.walk(git2::TreeWalkMode::PreOrder, |_, entry| {
+            if let Some(git2::ObjectType::Blob) = entry.kind() {
+                match odb.read_header(entry.id()) {
+                    Ok((blob_size, _)) => size += blob_size as u64,
+                    Err(e) => {
+                        result = Err(e);
+                        return git2::TreeWalkResult::Abort;
+                    }
+                }
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+        result?;
+        let cache = TreeSizeCache {
+            tree: tree.id().to_string(),
+            size,
+        };
+        std::fs::write(cache_path, serde_json::to_string(&cache)?)?;
+        Ok(size)
+    }
+    /// Find the last `RECENT_COMMIT_COUNT` first-parent commits from HEAD whose message is not
+    /// valid UTF-8 and the paths of the index which are not ASCII, along with where they are
+    pub fn detect_encoding_issues(&self) -> Vec<(String, EncodingIssue)> {
+        let inner = self.inner.lock().unwrap();
+        let mut issues = Vec::new();
+        if let Ok(mut revwalk) = inner.revwalk() {
+            if revwalk.push_head().is_ok() && revwalk.simplify_first_parent().is_ok() {
+                for oid in revwalk.take(RECENT_COMMIT_COUNT).flatten() {
+                    if let Ok(commit) = inner.find_commit(oid) {
+                        if std::str::from_utf8(commit.message_bytes()).is_err() {
+                            let short_id = oid.to_string()[..7].to_string();
+                            issues.push((short_id, EncodingIssue::InvalidUtf8CommitMessage(oid)));
+                        }
+                    }
+                }
+            }
+        }
+        if let Ok(index) = inner.index() {
+            for entry in index.iter() {
+                if !entry.path.is_ascii() {
+                    let path = String::from_utf8_lossy(&entry.path).into_owned();
+                    issues.push((path.clone(), EncodingIssue::NonAsciiPath(path)));
+                }
+            }
+        }
+        issues
+    }
+    /// Whether the repository stores files with Git LFS according to its attributes
+    pub fn lfs_enabled(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .workdir()
+            .and_then(|workdir| std::fs::read_to_string(workdir.join(".gitattributes")).ok())
+            .map(|attributes| attributes.contains("filter=lfs"))
+            .unwrap_or(false)
+    }
+    /// Download the LFS objects of the current checkout from a remote with `git-lfs`
+    pub fn fetch_lfs_objects(&self, remote: &str) -> Result<()> {
+        let workdir = {
+            let inner = self.inner.lock().unwrap();
+            inner
+                .workdir()
+                .ok_or_else(|| anyhow!("repository is bare"))?
+                .to_owned()
+        };
+        let output = process::Command::new("git-lfs")
+            .args(["pull", remote])
+            .current_dir(workdir)
+            .output()
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => anyhow!("git-lfs is not installed"),
+                _ => e.into(),
+            })?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "git-lfs failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+    /// Count the commits reachable from `to` but not from `from`, like `git rev-list --count from..to`
+    pub fn rev_count_range(&self, from: &str, to: &str) -> Result<usize> {
         let inner = self.inner.lock().unwrap();
-        let local_name = inner
-            .head()?
-            .name()
-            .ok_or_else(|| anyhow!("local name is not valid UTF-8"))?
-            .to_owned();
-        let remote_name = inner.branch_upstream_remote(&local_name)?;
-        let mut remote = inner.find_remote(
-            remote_name
-                .as_str()
-                .ok_or_else(|| anyhow!("remote name is not valid UTF-8"))?,
-        )?;
-
-        // Create credentials callback for SSH authentication
-        let mut callbacks = git2::RemoteCallbacks::new();
-        callbacks.credentials(|_, _, _| {
-            git2::Cred::ssh_key(
-                "git",
-                None,
-                std::path::Path::new(&format!("{}/.ssh/id_rsa", std::env::var("HOME").unwrap())),
-                None,
-            )
-        });
-        let mut fo = git2::FetchOptions::new();
-        fo.remote_callbacks(callbacks);
+        let from = inner.revparse_single(from)?.peel_to_commit()?.id();
+        let to = inner.revparse_single(to)?.peel_to_commit()?.id();
+        let mut revwalk = inner.revwalk()?;
+        revwalk.push(to)?;
+        revwalk.hide(from)?;
+        let mut count = 0;
+        for oid in revwalk {
+            oid?;
+            count += 1;
+        }
+        Ok(count)
+    }
+    /// Get the last `limit` commits reachable from `to` but not from `from`
+    pub fn range_commits(&self, from: &str, to: &str, limit: usize) -> Result<Vec<CommitInfo>> {
+        let inner = self.inner.lock().unwrap();
+        let from = inner.revparse_single(from)?.peel_to_commit()?.id();
+        let to = inner.revparse_single(to)?.peel_to_commit()?.id();
+        let mut revwalk = inner.revwalk()?;
+        revwalk.push(to)?;
+        revwalk.hide(from)?;
+        revwalk
+            .take(limit)
+            .map(|oid| Ok(CommitInfo::from(&inner.find_commit(oid?)?)))
+            .collect()
+    }
+    /// Get the paths with changes in the working tree, including untracked files
+    pub fn unstaged_paths(&self) -> Result<Vec<String>> {
+        let inner = self.inner.lock().unwrap();
+        unstaged_paths(&inner)
+    }
+    /// Restore files of the index and working tree to their content at HEAD
+    pub fn index_checkout(&self, paths: &[&str]) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        let mut builder = git2::build::CheckoutBuilder::new();
+        builder.force().allow_conflicts(false);
+        for path in paths {
+            builder.path(path);
+        }
+        Ok(inner.checkout_head(Some(&mut builder))?)
+    }
+    /// Get the hash of a file of the working tree as a blob
+    pub fn workdir_blob_id(&self, path: &str) -> Option<git2::Oid> {
+        let inner = self.inner.lock().unwrap();
+        let path = inner.workdir()?.join(path);
+        git2::Oid::hash_file(git2::ObjectType::Blob, path).ok()
+    }
+    /// Get the paths of the files excluded by the ignore rules
+    pub fn list_ignored_files(&self) -> Result<Vec<String>> {
+        let inner = self.inner.lock().unwrap();
+        let mut status_options = git2::StatusOptions::new();
+        status_options
+            .include_ignored(true)
+            .recurse_ignored_dirs(true)
+            .include_untracked(false);
+        let statuses = inner.statuses(Some(&mut status_options))?;
+        Ok(statuses
+            .iter()
+            .filter(|entry| entry.status().contains(FileStatus::IGNORED))
+            .filter_map(|entry| entry.path().map(String::from))
+            .collect())
+    }
+    /// Get the paths with staged changes
+    pub fn staged_paths(&self) -> Result<Vec<String>> {
+        let inner = self.inner.lock().unwrap();
+        let mut status_options = git2::StatusOptions::new();
+        status_options.show(git2::StatusShow::Index);
+        let statuses = inner.statuses(Some(&mut status_options))?;
+        Ok(statuses
+            .iter()
+            .filter(|entry| !entry.status().is_empty())
+            .filter_map(|entry| entry.path().map(String::from))
+            .collect())
+    }
+    /// Unstage changes like `git restore --staged`, restoring the index entries of `paths` to HEAD
+    pub fn index_remove(&self, paths: &[&str]) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        // Removing index entries would stage the deletion of files present at HEAD
+        let head = inner
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok());
+        Ok(inner.reset_default(head.as_ref().map(|head| head.as_object()), paths)?)
+    }
+    /// Stage all changes like `git add -A`, returning the number of staged files
+    pub fn index_add_all(&self) -> Result<usize> {
+        let inner = self.inner.lock().unwrap();
+        let count = unstaged_paths(&inner)?.len();
+        let mut index = inner.index()?;
+        index.add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)?;
+        // Adding does not stage deleted files
+        index.update_all(["*"].iter(), None)?;
+        index.write()?;
+        Ok(count)
+    }
+    /// Commit the current index with the identity of the git config, moving HEAD to the new commit
+    pub fn create_commit(&self, message: &str, parents: &[git2::Oid]) -> Result<git2::Oid> {
+        let inner = self.inner.lock().unwrap();
+        let signature = config_signature(&inner)?;
+        let tree = inner.find_tree(inner.index()?.write_tree()?)?;
+        let parents = parents
+            .iter()
+            .map(|oid| inner.find_commit(*oid))
+            .collect::<Result<Vec<_>, _>>()?;
+        let parents = parents.iter().collect::<Vec<_>>();
+        Ok(inner.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )?)
+    }
+    /// Commit the current index on top of HEAD signed with the key of `user.signingKey`,
+    /// unless `commit.gpgsign` is explicitly disabled
+    pub fn commit_with_gpg(&self, message: &str) -> Result<git2::Oid> {
+        // A repository without commits has an unborn HEAD
+        let parents = self.resolve_commit("HEAD").into_iter().collect::<Vec<_>>();
+        let sign = {
+            let inner = self.inner.lock().unwrap();
+            let config = inner.config()?;
+            config.get_bool("commit.gpgsign").unwrap_or(true)
+        };
+        if !sign {
+            return self.create_commit(message, &parents);
+        }
+        let signing_key = self.config_signing_key();
+        let inner = self.inner.lock().unwrap();
+        let signature = config_signature(&inner)?;
+        let tree = inner.find_tree(inner.index()?.write_tree()?)?;
+        let parents = parents
+            .iter()
+            .map(|oid| inner.find_commit(*oid))
+            .collect::<Result<Vec<_>, _>>()?;
+        let parents = parents.iter().collect::<Vec<_>>();
+        let buffer =
+            inner.commit_create_buffer(&signature, &signature, message, &tree, &parents)?;
+        let content = buffer
+            .as_str()
+            .ok_or_else(|| anyhow!("commit is not valid UTF-8"))?;
+        let gpg_signature = gpg_sign(signing_key.as_deref(), content.as_bytes())?;
+        let oid = inner.commit_signed(content, &gpg_signature, None)?;
 
-        // Fetch
-        Ok(remote.fetch(&[&local_name], Some(&mut fo), None)?)
+        // Unlike `commit`, `commit_signed` does not move HEAD
+        let head = inner.find_reference("HEAD")?;
+        match head.symbolic_target() {
+            Some(name) => {
+                inner.reference(name, oid, true, "glim: commit")?;
+            }
+            None => inner.set_head_detached(oid)?,
+        }
+        Ok(oid)
     }
     pub fn compute_status(&mut self) -> Result<()> {
         let inner = self.inner.lock().unwrap();
@@ -68,9 +1935,35 @@ impl Repository {
         self.status = Some(Status(set));
         Ok(())
     }
+    /// Compute the status on a thread of `pool`, sending back the repository along with the
+    /// result as the message built by `message` once done
+    pub fn compute_status_background<T: Send + 'static>(
+        mut self,
+        pool: &ThreadPool,
+        tx: mpsc::Sender<T>,
+        message: fn(Self, Result<()>) -> T,
+    ) {
+        pool.execute(move || {
+            let result = self.compute_status();
+            // The receiver is gone if it stopped waiting
+            let _ = tx.send(message(self, result));
+        });
+    }
     pub fn status(&self) -> Option<&Status> {
         self.status.as_ref()
     }
+    /// Get the commit HEAD points to, along with the current branch unless HEAD is detached
+    pub fn head_commit(&self) -> Result<(Option<String>, git2::Oid)> {
+        let inner = self.inner.lock().unwrap();
+        let head = inner.head()?;
+        let branch = if head.is_branch() {
+            head.shorthand().map(String::from)
+        } else {
+            None
+        };
+        let commit = head.peel_to_commit()?.id();
+        Ok((branch, commit))
+    }
     pub fn branch_name(&self) -> Option<String> {
         let inner = self.inner.lock().unwrap();
         let head_branch = git2::Branch::wrap(inner.head().ok()?);
@@ -91,13 +1984,53 @@ impl Repository {
             .ok()?
             .into_reference()
             .target()?;
-        match inner.graph_ahead_behind(local_oid, upstream_oid) {
-            Ok((0, 0)) => Some(Distance::Same),
-            Ok((a, b)) if a > 0 && b == 0 => Some(Distance::Ahead),
-            Ok((a, b)) if a == 0 && b > 0 => Some(Distance::Behind),
-            Ok((_, _)) => Some(Distance::Both),
-            Err(_) => None,
+        inner
+            .graph_ahead_behind(local_oid, upstream_oid)
+            .ok()
+            .map(Distance::from)
+    }
+    /// List the local branches with their upstream and distance to it, the current one first
+    pub fn branches(&self) -> Result<Vec<BranchInfo>> {
+        let inner = self.inner.lock().unwrap();
+        let mut branches = Vec::new();
+        for branch in inner.branches(Some(git2::BranchType::Local))? {
+            let (branch, _) = branch?;
+            let name = match branch.name()? {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let is_head = branch.is_head();
+            let (upstream, distance) = match branch.upstream() {
+                Ok(upstream) => {
+                    let distance = match (branch.get().target(), upstream.get().target()) {
+                        (Some(local), Some(remote)) => inner
+                            .graph_ahead_behind(local, remote)
+                            .ok()
+                            .map(Distance::from),
+                        _ => None,
+                    };
+                    (upstream.name()?.map(String::from), distance)
+                }
+                Err(_) => (None, None),
+            };
+            branches.push(BranchInfo {
+                name,
+                is_head,
+                upstream,
+                distance,
+            });
         }
+        branches.sort_by(|a, b| b.is_head.cmp(&a.is_head).then_with(|| a.name.cmp(&b.name)));
+        Ok(branches)
+    }
+    pub fn last_commit_time(&self, kind: TimeKind) -> Option<DateTime<FixedOffset>> {
+        let inner = self.inner.lock().unwrap();
+        let commit = inner.head().ok()?.peel_to_commit().ok()?;
+        let time = match kind {
+            TimeKind::Author => commit.author().when(),
+            TimeKind::Committer => commit.committer().when(),
+        };
+        Some(to_datetime(time))
     }
     pub fn commit_summary(&self) -> Option<String> {
         let inner = self.inner.lock().unwrap();
@@ -105,6 +2038,893 @@ impl Repository {
         let commit = inner.find_commit(head_oid).ok()?;
         commit.summary().map(String::from)
     }
+    pub fn commit_summary_of(&self, oid: git2::Oid) -> Option<String> {
+        let inner = self.inner.lock().unwrap();
+        let commit = inner.find_commit(oid).ok()?;
+        commit.summary().map(String::from)
+    }
+}
+
+/// Resolve a reference (branch, tag, commit, ...) to its tree
+///
+/// The tree borrows the repository, which is only reachable through the lock of
+/// `Repository`, so this cannot be a method of it.
+fn tree_at<'r>(repository: &'r git2::Repository, reference: &str) -> Result<git2::Tree<'r>> {
+    let object = repository.revparse_single(reference)?;
+    Ok(object.peel_to_tree()?)
+}
+
+/// Format a diff as a patch applicable with `git apply`, keeping the bytes of files which are
+/// not UTF-8
+fn diff_to_patch(diff: &git2::Diff) -> Result<Vec<u8>> {
+    let mut patch = Vec::new();
+    diff.print(git2::DiffFormat::Patch, |_, _, line| {
+        match line.origin() {
+            // Content lines do not include their origin
+            '+' | '-' | ' ' => {
+                patch.push(line.origin() as u8);
+                patch.extend_from_slice(line.content());
+            }
+            // The line before the marker lacks its newline, which `git apply` needs to find it
+            '=' | '>' | '<' => {
+                if !patch.ends_with(b"\n") {
+                    patch.push(b'\n');
+                }
+                patch.extend_from_slice(b"\\ No newline at end of file\n");
+            }
+            _ => patch.extend_from_slice(line.content()),
+        }
+        true
+    })?;
+    Ok(patch)
+}
+
+/// Get the commit of the stash at `index`, `stash_foreach` requiring a mutable repository
+fn stash_id(repository: &mut git2::Repository, index: usize) -> Result<git2::Oid> {
+    let mut stash = None;
+    repository.stash_foreach(|i, _, oid| {
+        if i == index {
+            stash = Some(*oid);
+        }
+        stash.is_none()
+    })?;
+    stash.ok_or_else(|| anyhow!("stash@{{{}}} does not exist", index))
+}
+
+/// Diff the tree of a stash commit against its first parent, the commit it was created on
+fn stash_diff(repository: &git2::Repository, oid: git2::Oid) -> Result<git2::Diff<'_>> {
+    let stash = repository.find_commit(oid)?;
+    let parent = stash.parent(0)?;
+    Ok(repository.diff_tree_to_tree(Some(&parent.tree()?), Some(&stash.tree()?), None)?)
+}
+
+/// Create an armored detached signature of `payload` with GPG
+#[cfg(feature = "gpg-verify")]
+fn gpg_sign(signing_key: Option<&str>, payload: &[u8]) -> Result<String> {
+    let mut context = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
+    context.set_armor(true);
+    if let Some(signing_key) = signing_key {
+        let key = context.get_secret_key(signing_key)?;
+        context.add_signer(&key)?;
+    }
+    let mut signature = Vec::new();
+    context.sign_detached(payload, &mut signature)?;
+    Ok(String::from_utf8(signature)?)
+}
+
+#[cfg(not(feature = "gpg-verify"))]
+fn gpg_sign(_signing_key: Option<&str>, _payload: &[u8]) -> Result<String> {
+    Err(anyhow!(
+        "signing requires glim to be built with the gpg-verify feature"
+    ))
+}
+
+/// Mark a commit during a bisection, as git does with a reference and a log entry
+fn bisect_mark(repository: &git2::Repository, term: &str, oid: git2::Oid) -> Result<()> {
+    let reference = match term {
+        "bad" => "refs/bisect/bad".to_string(),
+        _ => format!("refs/bisect/{}-{}", term, oid),
+    };
+    repository.reference(&reference, oid, true, &format!("bisect: {}", term))?;
+    let summary = repository
+        .find_commit(oid)?
+        .summary()
+        .unwrap_or_default()
+        .to_string();
+    bisect_log(repository, &format!("# {}: [{}] {}", term, oid, summary))
+}
+
+/// Append a line to `BISECT_LOG`
+fn bisect_log(repository: &git2::Repository, line: &str) -> Result<()> {
+    let mut log = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(repository.path().join("BISECT_LOG"))?;
+    Ok(writeln!(log, "{}", line)?)
+}
+
+/// Parse `info/grafts`, where each line is `<oid> [<parent-oid>...]`
+fn read_grafts(repository: &git2::Repository) -> Vec<(git2::Oid, Vec<git2::Oid>)> {
+    let content = match std::fs::read_to_string(repository.path().join("info").join("grafts")) {
+        Ok(content) => content,
+        Err(_) => return Vec::new(),
+    };
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut oids = line
+                .split_whitespace()
+                .map(|oid| git2::Oid::from_str(oid).ok());
+            let child = oids.next()??;
+            let parents = oids.collect::<Option<Vec<_>>>()?;
+            Some((child, parents))
+        })
+        .collect()
+}
+
+fn write_grafts(
+    repository: &git2::Repository,
+    grafts: &[(git2::Oid, Vec<git2::Oid>)],
+) -> Result<()> {
+    let info = repository.path().join("info");
+    std::fs::create_dir_all(&info)?;
+    let content = grafts
+        .iter()
+        .map(|(child, parents)| {
+            std::iter::once(child)
+                .chain(parents)
+                .map(git2::Oid::to_string)
+                .collect::<Vec<_>>()
+                .join(" ")
+                + "\n"
+        })
+        .collect::<String>();
+    Ok(std::fs::write(info.join("grafts"), content)?)
+}
+
+fn unstaged_paths(repository: &git2::Repository) -> Result<Vec<String>> {
+    let mut status_options = git2::StatusOptions::new();
+    status_options
+        .show(git2::StatusShow::Workdir)
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false);
+    let statuses = repository.statuses(Some(&mut status_options))?;
+    Ok(statuses
+        .iter()
+        .filter(|entry| !entry.status().is_empty())
+        .filter_map(|entry| entry.path().map(String::from))
+        .collect())
+}
+
+/// Apply the first mailmap entry matching an author, preferring those matching the name too
+pub fn normalize_author(mailmap: &[MailmapEntry], name: &str, email: &str) -> (String, String) {
+    let matching = mailmap
+        .iter()
+        .filter(|entry| entry.match_email.eq_ignore_ascii_case(email))
+        .filter(|entry| match &entry.match_name {
+            Some(match_name) => match_name.eq_ignore_ascii_case(name),
+            None => true,
+        })
+        .max_by_key(|entry| entry.match_name.is_some());
+    match matching {
+        Some(entry) => (
+            entry.canonical_name.as_deref().unwrap_or(name).to_string(),
+            entry
+                .canonical_email
+                .as_deref()
+                .unwrap_or(email)
+                .to_string(),
+        ),
+        None => (name.to_string(), email.to_string()),
+    }
+}
+
+/// Collect the trees and blobs reachable from a tree, skipping the trees already collected
+fn count_tree_objects(
+    repository: &git2::Repository,
+    tree_id: git2::Oid,
+    trees: &mut HashSet<git2::Oid>,
+    blobs: &mut HashSet<git2::Oid>,
+) -> Result<()> {
+    if !trees.insert(tree_id) {
+        return Ok(());
+    }
+    let tree = repository.find_tree(tree_id)?;
+    for entry in tree.iter() {
+        match entry.kind() {
+            Some(git2::ObjectType::Tree) => {
+                count_tree_objects(repository, entry.id(), trees, blobs)?
+            }
+            Some(git2::ObjectType::Blob) => {
+                blobs.insert(entry.id());
+            }
+            // Submodules are commits of other repositories
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Hash the names and targets of all references to detect when they change
+fn refs_hash(repository: &git2::Repository) -> Result<String> {
+    let mut refs = repository
+        .references()?
+        .flatten()
+        .filter_map(|reference| Some(format!("{} {}", reference.name()?, reference.target()?)))
+        .collect::<Vec<_>>();
+    refs.sort();
+    let oid = git2::Oid::hash_object(git2::ObjectType::Blob, refs.join("\n").as_bytes())?;
+    Ok(oid.to_string())
+}
+
+/// Update a submodule, initializing it first if `init`, then its nested submodules if `recursive`
+fn update_submodule(
+    repository: &git2::Repository,
+    name: &str,
+    init: bool,
+    recursive: bool,
+) -> Result<()> {
+    let mut submodule = repository.find_submodule(name)?;
+    let mut fo = git2::FetchOptions::new();
+    fo.remote_callbacks(remote_callbacks());
+    let mut options = git2::SubmoduleUpdateOptions::new();
+    options.fetch(fo);
+    if submodule.open().is_ok() {
+        submodule.update(init, Some(&mut options))?;
+    } else {
+        // libgit2 refuses to clone into the empty directory checked out in place of the submodule
+        let workdir = repository
+            .workdir()
+            .ok_or_else(|| anyhow!("repository is bare"))?;
+        let mut command = process::Command::new("git");
+        command.args(["submodule", "update"]).current_dir(workdir);
+        if init {
+            command.arg("--init");
+        }
+        let output = command.arg("--").arg(submodule.path()).output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "git submodule update failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
+    if recursive {
+        // Nested submodules cannot have been initialized before their parent was checked out
+        let nested = submodule.open()?;
+        for nested_submodule in nested.submodules()? {
+            if let Some(nested_name) = nested_submodule.name() {
+                update_submodule(&nested, nested_name, true, recursive)
+                    .map_err(|e| e.context(format!("failed to update '{}'", nested_name)))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Create a signature for now from `user.name` and `user.email`
+fn config_signature(repository: &git2::Repository) -> Result<git2::Signature<'static>> {
+    let config = repository.config()?;
+    let name = config
+        .get_string("user.name")
+        .map_err(|_| anyhow!("user.name is not set"))?;
+    let email = config
+        .get_string("user.email")
+        .map_err(|_| anyhow!("user.email is not set"))?;
+    Ok(git2::Signature::now(&name, &email)?)
+}
+
+/// Number of first-parent commits from HEAD inspected by `detect_large_commits`
+const RECENT_COMMIT_COUNT: usize = 100;
+
+/// Reference of the notes listed by `notes_list`, the default of `git notes`
+const NOTES_REF: &str = "refs/notes/commits";
+
+/// Client-side hooks which can be wrapped to log their failures
+const HOOKS: &[&str] = &[
+    "applypatch-msg",
+    "pre-applypatch",
+    "post-applypatch",
+    "pre-commit",
+    "prepare-commit-msg",
+    "commit-msg",
+    "post-commit",
+    "pre-rebase",
+    "post-checkout",
+    "post-merge",
+    "pre-push",
+    "post-rewrite",
+];
+
+/// Shell script running the original hook and appending a JSON line to the log when it fails
+const HOOK_SHIM: &str = r#"#!/bin/sh
+# Installed by glim to log failures of the original hook
+original="$(dirname "$0")/{hook}.glim-orig"
+log="$(git rev-parse --git-dir)/glim_hook_log.json"
+stderr_file="$(mktemp)"
+"$original" "$@" 2> "$stderr_file"
+code=$?
+cat "$stderr_file" >&2
+if [ $code -ne 0 ]; then
+    # Escape the output as a JSON string, only with POSIX tools
+    stderr="$(tr -d '\000-\010\013-\037' < "$stderr_file" | awk '{
+        escaped = ""
+        for (i = 1; i <= length($0); i++) {
+            c = substr($0, i, 1)
+            if (c == "\\" || c == "\"") c = "\\" c
+            else if (c == "\t") c = "\\t"
+            escaped = escaped c
+        }
+        printf "%s\\n", escaped
+    }')"
+    printf '{"hook":"%s","timestamp":%s,"exit_code":%s,"stderr":"%s"}\n' "{hook}" "$(date +%s)" "$code" "$stderr" >> "$log"
+fi
+rm -f "$stderr_file"
+exit $code
+"#;
+
+/// Check out the tree of a reference and point HEAD to it
+fn checkout_reference(repository: &git2::Repository, reference: &git2::Reference) -> Result<()> {
+    let commit = reference.peel_to_commit()?;
+    repository.checkout_tree(
+        commit.as_object(),
+        Some(&mut git2::build::CheckoutBuilder::new()),
+    )?;
+    let name = reference
+        .name()
+        .ok_or_else(|| anyhow!("reference name is not valid UTF-8"))?;
+    Ok(repository.set_head(name)?)
+}
+
+/// Move a local branch to `target` if it is behind it and not ahead, returning the numbers of
+/// commits it is ahead and behind
+///
+/// The working tree is updated first if the branch is checked out, failing on local changes
+/// which would be overwritten.
+fn fast_forward_reference(
+    repository: &git2::Repository,
+    reference: &git2::Reference,
+    target: git2::Oid,
+    log_message: &str,
+) -> Result<(usize, usize)> {
+    let name = reference
+        .name()
+        .ok_or_else(|| anyhow!("reference name is not valid UTF-8"))?;
+    let local_oid = reference
+        .target()
+        .ok_or_else(|| anyhow!("'{}' is not a direct reference", name))?;
+    let (ahead, behind) = repository.graph_ahead_behind(local_oid, target)?;
+    if ahead > 0 || behind == 0 {
+        return Ok((ahead, behind));
+    }
+    let is_head = repository
+        .head()
+        .is_ok_and(|head| head.name() == Some(name));
+    if is_head {
+        repository.checkout_tree(
+            repository.find_commit(target)?.as_object(),
+            Some(git2::build::CheckoutBuilder::new().safe()),
+        )?;
+    }
+    repository
+        .find_reference(name)?
+        .set_target(target, log_message)?;
+    Ok((ahead, behind))
+}
+
+/// Reset the index and working tree to HEAD, discarding changes
+fn reset_to_head(repository: &git2::Repository) -> Result<()> {
+    let head = repository.head()?.peel_to_commit()?;
+    let mut checkout = git2::build::CheckoutBuilder::new();
+    checkout.force();
+    Ok(repository.reset(head.as_object(), git2::ResetType::Hard, Some(&mut checkout))?)
+}
+
+/// Get a short name of a repository state
+pub fn state_name(state: git2::RepositoryState) -> &'static str {
+    use git2::RepositoryState::*;
+    match state {
+        Clean => "clean",
+        Merge => "merge",
+        Revert | RevertSequence => "revert",
+        CherryPick | CherryPickSequence => "cherry-pick",
+        Bisect => "bisect",
+        Rebase | RebaseInteractive | RebaseMerge => "rebase",
+        ApplyMailbox | ApplyMailboxOrRebase => "am",
+    }
+}
+
+/// Push refspecs to a remote, failing if the remote rejects any of them
+fn push_refspecs(
+    repository: &git2::Repository,
+    remote_name: &str,
+    refspecs: &[String],
+) -> Result<()> {
+    let mut remote = repository
+        .find_remote(remote_name)
+        .map_err(|_| anyhow!("remote '{}' does not exist", remote_name))?;
+    let mut callbacks = remote_callbacks();
+    callbacks.push_update_reference(|refname, status| match status {
+        Some(message) => Err(git2::Error::from_str(&format!(
+            "failed to push '{}': {}",
+            refname, message
+        ))),
+        None => Ok(()),
+    });
+    let mut po = git2::PushOptions::new();
+    po.remote_callbacks(callbacks);
+    Ok(remote.push(refspecs, Some(&mut po))?)
+}
+
+/// Whether a connection to a remote of the repository at `path` can be established
+fn remote_reachable(path: &Path, remote_name: &str) -> bool {
+    let connect = || -> Result<()> {
+        let repository = git2::Repository::open(path)?;
+        let mut remote = repository.find_remote(remote_name)?;
+        remote.connect_auth(git2::Direction::Fetch, Some(remote_callbacks()), None)?;
+        remote.disconnect()?;
+        Ok(())
+    };
+    connect().is_ok()
+}
+
+/// List the names of the references advertised by a remote
+fn remote_ref_names(repository: &git2::Repository, remote_name: &str) -> Result<HashSet<String>> {
+    let mut remote = repository
+        .find_remote(remote_name)
+        .map_err(|_| anyhow!("remote '{}' does not exist", remote_name))?;
+    let connection = remote.connect_auth(git2::Direction::Push, Some(remote_callbacks()), None)?;
+    let names = connection
+        .list()?
+        .iter()
+        .map(|head| head.name().to_string())
+        .collect();
+    Ok(names)
+}
+
+/// Path of the SSH key used to authenticate to remotes
+fn ssh_key_path() -> PathBuf {
+    Path::new(&std::env::var("HOME").unwrap_or_default()).join(".ssh/id_rsa")
+}
+
+/// Total size of the files in a directory tree, ignoring those which cannot be read
+fn directory_size(path: &Path) -> u64 {
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| match entry.file_type() {
+                    Ok(file_type) if file_type.is_dir() => directory_size(&entry.path()),
+                    Ok(file_type) if file_type.is_file() => {
+                        entry.metadata().map(|metadata| metadata.len()).unwrap_or(0)
+                    }
+                    _ => 0,
+                })
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+/// Create remote callbacks with credentials for SSH authentication
+fn remote_callbacks<'a>() -> git2::RemoteCallbacks<'a> {
+    let mut callbacks = git2::RemoteCallbacks::new();
+    callbacks.credentials(|_, _, _| git2::Cred::ssh_key("git", None, &ssh_key_path(), None));
+    callbacks
+}
+
+/// References whose target object does not exist
+#[derive(Debug, thiserror::Error)]
+#[error("broken references: {}", .0.join(", "))]
+pub struct BrokenReferences(pub Vec<String>);
+
+/// Problem of a repository found by `glim doctor`
+pub enum Problem {
+    MissingPath,
+    NotRepository,
+    UnbornHead,
+    DetachedHead,
+    NoUpstream,
+    UnexpectedBranch(String),
+    MissingSshKey,
+    UnreachableRemote(String),
+    Other(String),
+}
+
+impl Problem {
+    /// How to fix the problem
+    pub fn suggestion(&self) -> String {
+        match self {
+            Problem::MissingPath => "remove it with `glim prune` or `glim remove`".to_string(),
+            Problem::NotRepository => "remove it with `glim remove`".to_string(),
+            Problem::UnbornHead => "commit on the current branch".to_string(),
+            Problem::DetachedHead => "switch to a branch with `glim checkout`".to_string(),
+            Problem::NoUpstream => "set one with `git branch --set-upstream-to`".to_string(),
+            Problem::UnexpectedBranch(branch) => {
+                format!("switch to '{}' with `glim checkout`", branch)
+            }
+            Problem::MissingSshKey => {
+                format!("create {} with `ssh-keygen`", ssh_key_path().display())
+            }
+            Problem::UnreachableRemote(remote) => {
+                format!("check the URL of '{}' with `git remote -v`", remote)
+            }
+            Problem::Other(_) => String::new(),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::MissingPath => write!(f, "path does not exist"),
+            Problem::NotRepository => write!(f, "not a git repository"),
+            Problem::UnbornHead => write!(f, "no commits on the current branch"),
+            Problem::DetachedHead => write!(f, "HEAD is detached"),
+            Problem::NoUpstream => write!(f, "no upstream"),
+            Problem::UnexpectedBranch(branch) => {
+                write!(f, "not on the expected branch '{}'", branch)
+            }
+            Problem::MissingSshKey => write!(f, "SSH key not found"),
+            Problem::UnreachableRemote(remote) => write!(f, "remote '{}' is unreachable", remote),
+            Problem::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Outcome of `Repository::switch_branch`
+pub enum BranchSwitch {
+    /// The branch was already checked out
+    Unchanged,
+    Switched,
+    /// The branch was created to track this remote branch
+    Created(String),
+    /// Neither the branch nor a remote branch of the same name exists
+    Missing,
+}
+
+pub struct BranchInfo {
+    pub name: String,
+    pub is_head: bool,
+    pub upstream: Option<String>,
+    pub distance: Option<Distance>,
+}
+
+pub struct CommitInfo {
+    pub oid: git2::Oid,
+    pub author: String,
+    pub email: String,
+    pub time: DateTime<FixedOffset>,
+    pub summary: String,
+}
+
+impl CommitInfo {
+    pub fn short_id(&self) -> String {
+        self.oid.to_string().chars().take(7).collect()
+    }
+}
+
+impl From<&git2::Commit<'_>> for CommitInfo {
+    fn from(commit: &git2::Commit) -> Self {
+        Self {
+            oid: commit.id(),
+            author: commit.author().name().unwrap_or_default().to_string(),
+            email: commit.author().email().unwrap_or_default().to_string(),
+            time: to_datetime(commit.time()),
+            summary: commit.summary().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// How to name a repository
+pub enum NamingStrategy {
+    /// Last component of the path (e.g. `repo`)
+    LastComponent,
+    /// Last two components of the path (e.g. `company/repo`)
+    LastTwoComponents,
+    /// User and repository of the remote URL (e.g. `user/repo`)
+    RemoteSlug,
+    Custom(String),
+}
+
+impl NamingStrategy {
+    /// Name a repository from its path, falling back to the last component for remote slugs
+    pub fn name_from_path(&self, path: &Path) -> Result<String> {
+        let count = match self {
+            NamingStrategy::Custom(name) => return Ok(name.clone()),
+            NamingStrategy::LastTwoComponents => 2,
+            NamingStrategy::LastComponent | NamingStrategy::RemoteSlug => 1,
+        };
+        let mut components = path
+            .components()
+            .filter_map(|component| match component {
+                std::path::Component::Normal(component) => Some(component),
+                _ => None,
+            })
+            .rev()
+            .take(count)
+            .map(|component| {
+                component
+                    .to_str()
+                    .ok_or_else(|| anyhow!("path is not valid UTF-8"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if components.is_empty() {
+            return Err(anyhow!("path is too short"));
+        }
+        components.reverse();
+        Ok(components.join("/"))
+    }
+}
+
+impl FromStr for NamingStrategy {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "last-component" => Ok(NamingStrategy::LastComponent),
+            "last-two-components" => Ok(NamingStrategy::LastTwoComponents),
+            "remote-slug" => Ok(NamingStrategy::RemoteSlug),
+            _ => match s.strip_prefix("custom:") {
+                Some(name) if !name.is_empty() => Ok(NamingStrategy::Custom(name.to_string())),
+                _ => Err("expected 'last-component', 'last-two-components', 'remote-slug' or 'custom:<NAME>'"),
+            },
+        }
+    }
+}
+
+/// Extract `user/repo` from a remote URL such as `git@host:user/repo.git` or `https://host/user/repo`
+fn remote_slug(url: &str) -> Option<String> {
+    let url = url.trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    let mut segments = url.rsplit(['/', ':']);
+    let repo = segments.next().filter(|s| !s.is_empty())?;
+    let user = segments.next().filter(|s| !s.is_empty())?;
+    Some(format!("{}/{}", user, repo))
+}
+
+/// Translate a remote URL such as `git@host:user/repo.git` to the HTTPS URL of its web page
+fn web_url(url: &str) -> Option<String> {
+    let url = url.trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    let (host, path) = match url.split_once("://") {
+        Some((scheme, rest)) => {
+            let (host, path) = rest.split_once('/')?;
+            // The port of SSH and git URLs is not the one of the web server
+            match scheme {
+                "ssh" | "git" => (host.split(':').next()?, path),
+                "http" | "https" => (host, path),
+                _ => return None,
+            }
+        }
+        // scp-like syntax, local paths having no colon
+        None => url.split_once(':')?,
+    };
+    let host = host.rsplit('@').next()?;
+    if host.is_empty() || path.is_empty() || path.starts_with('/') {
+        return None;
+    }
+    Some(format!("https://{}/{}", host, path))
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ObjectCounts {
+    pub commits: usize,
+    pub trees: usize,
+    pub blobs: usize,
+    pub tags: usize,
+}
+
+impl fmt::Display for ObjectCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} commits, {} trees, {} blobs, {} tags",
+            self.commits, self.trees, self.blobs, self.tags
+        )
+    }
+}
+
+/// Object counts stored along with the state of the references they were computed for
+#[derive(Serialize, Deserialize)]
+struct ObjectCountsCache {
+    refs_hash: String,
+    counts: ObjectCounts,
+}
+
+#[derive(Serialize)]
+pub struct DiagnosticReport {
+    pub is_bare: bool,
+    pub is_shallow: bool,
+    pub is_empty: bool,
+    pub has_upstream: bool,
+    pub index_locked: bool,
+    pub loose_object_count: usize,
+    pub pack_count: usize,
+    pub conflict_count: usize,
+    pub stash_count: usize,
+    pub note_count: usize,
+    pub worktree_count: usize,
+    pub submodule_count: usize,
+    #[serde(serialize_with = "serialize_state")]
+    pub pending_operation: Option<git2::RepositoryState>,
+}
+
+impl fmt::Display for DiagnosticReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "bare: {}", self.is_bare)?;
+        writeln!(f, "shallow: {}", self.is_shallow)?;
+        writeln!(f, "empty: {}", self.is_empty)?;
+        writeln!(f, "upstream: {}", self.has_upstream)?;
+        writeln!(f, "index locked: {}", self.index_locked)?;
+        writeln!(f, "loose objects: {}", self.loose_object_count)?;
+        writeln!(f, "packs: {}", self.pack_count)?;
+        writeln!(f, "conflicts: {}", self.conflict_count)?;
+        writeln!(f, "stashes: {}", self.stash_count)?;
+        writeln!(f, "notes: {}", self.note_count)?;
+        writeln!(f, "worktrees: {}", self.worktree_count)?;
+        writeln!(f, "submodules: {}", self.submodule_count)?;
+        write!(
+            f,
+            "pending operation: {}",
+            self.pending_operation.map_or("none", state_name)
+        )
+    }
+}
+
+fn serialize_state<S: serde::Serializer>(
+    state: &Option<git2::RepositoryState>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    state.map(state_name).serialize(serializer)
+}
+
+pub enum EncodingIssue {
+    InvalidUtf8CommitMessage(git2::Oid),
+    NonAsciiPath(String),
+}
+
+impl fmt::Display for EncodingIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingIssue::InvalidUtf8CommitMessage(oid) => {
+                write!(f, "message of {} is not valid UTF-8", oid)
+            }
+            EncodingIssue::NonAsciiPath(path) => write!(f, "path '{}' is not ASCII", path),
+        }
+    }
+}
+
+/// Line of a mailmap mapping the identity of commits to a canonical one
+pub struct MailmapEntry {
+    pub canonical_name: Option<String>,
+    pub canonical_email: Option<String>,
+    pub match_name: Option<String>,
+    pub match_email: String,
+}
+
+impl MailmapEntry {
+    /// Parse a line of the forms
+    /// `Name <commit@email>`, `<proper@email> <commit@email>`,
+    /// `Name <proper@email> <commit@email>` and `Name <proper@email> Commit Name <commit@email>`
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.split('#').next()?;
+        let mut identities = Vec::new();
+        let mut rest = line;
+        while let Some(start) = rest.find('<') {
+            let end = start + rest[start..].find('>')?;
+            let name = rest[..start].trim();
+            let name = if name.is_empty() {
+                None
+            } else {
+                Some(name.to_string())
+            };
+            identities.push((name, rest[start + 1..end].trim().to_string()));
+            rest = &rest[end + 1..];
+        }
+        let mut identities = identities.into_iter();
+        match (identities.next(), identities.next()) {
+            (Some((canonical_name, match_email)), None) => Some(Self {
+                canonical_name,
+                canonical_email: None,
+                match_name: None,
+                match_email,
+            }),
+            (Some((canonical_name, canonical_email)), Some((match_name, match_email))) => {
+                Some(Self {
+                    canonical_name,
+                    canonical_email: Some(canonical_email),
+                    match_name,
+                    match_email,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Size of a tree stored along with its OID
+#[derive(Serialize, Deserialize)]
+struct TreeSizeCache {
+    tree: String,
+    size: u64,
+}
+
+pub struct DiffStat {
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl fmt::Display for DiffStat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} files changed, {} insertions(+), {} deletions(-)",
+            self.files_changed, self.insertions, self.deletions
+        )
+    }
+}
+
+pub struct NoteEntry {
+    pub annotated_oid: git2::Oid,
+    pub note_oid: git2::Oid,
+    pub content: String,
+}
+
+#[derive(Deserialize)]
+pub struct HookFailure {
+    pub hook: String,
+    pub timestamp: u64,
+    pub exit_code: i32,
+    pub stderr: String,
+}
+
+pub struct SyncResult {
+    /// Whether the upstream branch had new commits
+    pub fetched: bool,
+    /// Number of commits of the local branch missing from the upstream one before syncing
+    pub ahead: usize,
+    /// Number of commits of the upstream branch missing from the local one before syncing
+    pub behind: usize,
+    /// Number of commits the local branch was fast-forwarded by
+    pub merged_commits: usize,
+    /// Whether merging the diverged histories would conflict
+    pub conflicts: bool,
+}
+
+/// Next step of a bisection
+pub enum BisectStep {
+    /// Commit checked out for testing, with the number of commits left to test
+    Test(git2::Oid, usize),
+    /// First bad commit
+    Found(git2::Oid),
+}
+
+/// Which timestamp of a commit to use
+#[derive(Clone, Copy)]
+pub enum TimeKind {
+    Author,
+    Committer,
+}
+
+impl FromStr for TimeKind {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "author" => Ok(TimeKind::Author),
+            "committer" => Ok(TimeKind::Committer),
+            _ => Err("expected 'author' or 'committer'"),
+        }
+    }
+}
+
+fn to_datetime(time: git2::Time) -> DateTime<FixedOffset> {
+    let offset = FixedOffset::east_opt(time.offset_minutes() * 60)
+        .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+    offset.timestamp_opt(time.seconds(), 0).unwrap()
 }
 
 pub struct Status(HashSet<git2::Status>);
@@ -144,21 +2964,56 @@ impl fmt::Display for Status {
     }
 }
 
+/// Distance to the upstream, with the number of commits ahead and behind it
 pub enum Distance {
     Same,
-    Ahead,
-    Behind,
-    Both,
+    Ahead(usize),
+    Behind(usize),
+    Both(usize, usize),
+}
+
+impl From<(usize, usize)> for Distance {
+    fn from((ahead, behind): (usize, usize)) -> Self {
+        match (ahead, behind) {
+            (0, 0) => Distance::Same,
+            (a, 0) => Distance::Ahead(a),
+            (0, b) => Distance::Behind(b),
+            (a, b) => Distance::Both(a, b),
+        }
+    }
+}
+
+impl Distance {
+    /// Compact symbol of the distance, without the number of commits
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Distance::Same => "==",
+            Distance::Ahead(_) => ">>",
+            Distance::Behind(_) => "<<",
+            Distance::Both(_, _) => "<>",
+        }
+    }
+    pub fn ahead(&self) -> usize {
+        match self {
+            Distance::Ahead(ahead) | Distance::Both(ahead, _) => *ahead,
+            _ => 0,
+        }
+    }
+    pub fn behind(&self) -> usize {
+        match self {
+            Distance::Behind(behind) | Distance::Both(_, behind) => *behind,
+            _ => 0,
+        }
+    }
 }
 
 impl fmt::Display for Distance {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let symbol = match self {
-            Distance::Same => "==",
-            Distance::Ahead => ">>",
-            Distance::Behind => "<<",
-            Distance::Both => "<>",
-        };
-        write!(f, "{}", symbol)
+        match self {
+            Distance::Same => write!(f, "=="),
+            Distance::Ahead(ahead) => write!(f, "↑{}", ahead),
+            Distance::Behind(behind) => write!(f, "↓{}", behind),
+            Distance::Both(ahead, behind) => write!(f, "↑{} ↓{}", ahead, behind),
+        }
     }
 }
@@ -0,0 +1,380 @@
+use crate::open::{open_in_editor, open_with_system};
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender};
+use std::time::Duration;
+
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
+use ratatui::Terminal;
+
+/// Row of the table, each cell having an optional color
+pub struct TableRow {
+    /// Name of the repository, whose details are shown when the row is selected
+    pub name: String,
+    pub path: PathBuf,
+    pub cells: Vec<(String, Option<Color>)>,
+}
+
+/// Request sent to the thread refreshing the repositories
+pub enum Request {
+    /// Refresh every repository
+    Refresh,
+    /// Fetch a repository
+    Fetch(String),
+    /// Fetch a repository and fast-forward its current branch
+    Pull(String),
+}
+
+/// Message sent by the thread refreshing the repositories
+pub enum Update {
+    /// A refresh started
+    Refreshing,
+    /// A refresh finished with these rows and a summary of its errors
+    Rows(Vec<TableRow>, Option<String>),
+    /// The row of a repository changed after a request
+    Row(TableRow),
+    /// Result of a request, shown until the next one
+    Message(String),
+}
+
+/// Restore the terminal when the interface exits, even on errors
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> Result<Self> {
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+    }
+}
+
+struct App {
+    headers: Vec<String>,
+    rows: Vec<TableRow>,
+    errors: Option<String>,
+    message: Option<String>,
+    refreshing: bool,
+    /// Column the rows are sorted by, in the order they were received if `None`
+    sort_column: Option<usize>,
+    reverse: bool,
+    filter: String,
+    editing_filter: bool,
+    state: TableState,
+    /// Lines describing the selected repository, shown in a pane below the table
+    detail: Option<Vec<String>>,
+}
+
+impl App {
+    /// Indices of the rows matching the filter, in display order
+    fn visible_rows(&self) -> Vec<usize> {
+        let filter = self.filter.to_lowercase();
+        let mut indices = (0..self.rows.len())
+            .filter(|&i| {
+                filter.is_empty()
+                    || self.rows[i]
+                        .cells
+                        .iter()
+                        .any(|(content, _)| content.to_lowercase().contains(&filter))
+            })
+            .collect::<Vec<_>>();
+        if let Some(column) = self.sort_column {
+            // Sorting is stable so rows with the same content keep their order
+            indices.sort_by(|&a, &b| {
+                self.rows[a].cells[column]
+                    .0
+                    .cmp(&self.rows[b].cells[column].0)
+            });
+        }
+        if self.reverse {
+            indices.reverse();
+        }
+        indices
+    }
+    fn selected_row(&self) -> Option<&TableRow> {
+        let visible_rows = self.visible_rows();
+        let i = *visible_rows.get(self.state.selected()?)?;
+        Some(&self.rows[i])
+    }
+    fn selected_name(&self) -> Option<&str> {
+        self.selected_row().map(|row| row.name.as_str())
+    }
+    fn select(&mut self, offset: isize) {
+        let count = self.visible_rows().len();
+        if count == 0 {
+            self.state.select(None);
+            return;
+        }
+        let selected = self.state.selected().unwrap_or(0) as isize + offset;
+        self.state
+            .select(Some(selected.clamp(0, count as isize - 1) as usize));
+    }
+    /// Select the row of a repository again after the rows changed, or keep the selection in
+    /// range if it is not visible anymore
+    fn reselect(&mut self, name: Option<&str>) {
+        let position = name.and_then(|name| {
+            self.visible_rows()
+                .iter()
+                .position(|&i| self.rows[i].name == name)
+        });
+        match position {
+            Some(position) => self.state.select(Some(position)),
+            None => self.select(0),
+        }
+    }
+    fn footer(&self) -> String {
+        if self.editing_filter {
+            return format!("/{}", self.filter);
+        }
+        let mut parts = Vec::new();
+        if self.refreshing {
+            parts.push("refreshing...".to_string());
+        }
+        if let Some(message) = &self.message {
+            parts.push(message.clone());
+        }
+        if let Some(errors) = &self.errors {
+            parts.push(errors.clone());
+        }
+        let sort = match self.sort_column {
+            Some(column) => self.headers[column].to_lowercase(),
+            None => "none".to_string(),
+        };
+        parts.push(format!(
+            "q quit  j/k move  enter details  s sort ({}{})  / filter  f refresh all  F fetch  p pull  o editor  O files",
+            sort,
+            if self.reverse { ", reversed" } else { "" }
+        ));
+        parts.join("  |  ")
+    }
+}
+
+/// Show the rows received on `updates` in an interactive table until the user quits, sending
+/// refreshes and actions on `requests` and getting the lines of the detail pane of a repository
+/// from `details`
+pub fn run(
+    headers: Vec<String>,
+    updates: Receiver<Update>,
+    requests: Sender<Request>,
+    details: impl Fn(&str) -> Vec<String>,
+) -> Result<()> {
+    let _guard = TerminalGuard::new()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let mut app = App {
+        headers,
+        rows: Vec::new(),
+        errors: None,
+        message: None,
+        refreshing: false,
+        sort_column: None,
+        reverse: false,
+        filter: String::new(),
+        editing_filter: false,
+        state: TableState::default(),
+        detail: None,
+    };
+
+    loop {
+        while let Ok(update) = updates.try_recv() {
+            match update {
+                Update::Refreshing => app.refreshing = true,
+                Update::Rows(rows, errors) => {
+                    let selected = app.selected_name().map(String::from);
+                    app.rows = rows;
+                    app.errors = errors;
+                    app.refreshing = false;
+                    app.reselect(selected.as_deref());
+                    if app.detail.is_some() {
+                        app.detail = app.selected_name().map(&details);
+                    }
+                }
+                Update::Row(row) => {
+                    let selected = app.selected_name().map(String::from);
+                    if let Some(existing) = app.rows.iter_mut().find(|r| r.name == row.name) {
+                        *existing = row;
+                    }
+                    // The row may have moved if the rows are sorted or filtered
+                    app.reselect(selected.as_deref());
+                    if app.detail.is_some() {
+                        app.detail = app.selected_name().map(&details);
+                    }
+                }
+                Update::Message(message) => app.message = Some(message),
+            }
+        }
+
+        terminal.draw(|frame| draw(frame, &mut app))?;
+
+        // Poll so that updates are drawn even without input
+        if !event::poll(Duration::from_millis(200))? {
+            continue;
+        }
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        if app.editing_filter {
+            match key.code {
+                KeyCode::Enter => app.editing_filter = false,
+                KeyCode::Esc => {
+                    app.filter.clear();
+                    app.editing_filter = false;
+                }
+                KeyCode::Backspace => {
+                    app.filter.pop();
+                }
+                KeyCode::Char(c) => app.filter.push(c),
+                _ => {}
+            }
+            app.select(0);
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') => break,
+            KeyCode::Esc if app.detail.is_some() => app.detail = None,
+            KeyCode::Esc => break,
+            KeyCode::Enter => {
+                app.detail = match app.detail {
+                    Some(_) => None,
+                    None => app.selected_name().map(&details),
+                };
+                continue;
+            }
+            KeyCode::Down | KeyCode::Char('j') => app.select(1),
+            KeyCode::Up | KeyCode::Char('k') => app.select(-1),
+            KeyCode::PageDown => app.select(10),
+            KeyCode::PageUp => app.select(-10),
+            KeyCode::Char('s') => {
+                // Cycle through the columns, then back to the received order
+                app.sort_column = match app.sort_column {
+                    None => Some(0),
+                    Some(column) if column + 1 < app.headers.len() => Some(column + 1),
+                    Some(_) => None,
+                };
+            }
+            KeyCode::Char('r') => app.reverse = !app.reverse,
+            KeyCode::Char('/') => app.editing_filter = true,
+            KeyCode::Char('f') => {
+                let _ = requests.send(Request::Refresh);
+            }
+            KeyCode::Char('F') | KeyCode::Char('p') => {
+                if let Some(name) = app.selected_name().map(String::from) {
+                    let (request, action) = if key.code == KeyCode::Char('F') {
+                        (Request::Fetch(name.clone()), "fetching")
+                    } else {
+                        (Request::Pull(name.clone()), "pulling")
+                    };
+                    app.message = Some(format!("{}: {}...", name, action));
+                    let _ = requests.send(request);
+                }
+            }
+            KeyCode::Char('o') => {
+                if let Some(path) = app.selected_row().map(|row| row.path.clone()) {
+                    // The editor takes over the terminal until it exits
+                    let mut result = Ok(());
+                    suspend(&mut terminal, || result = open_in_editor(&path))?;
+                    app.message = result.err().map(|e| e.to_string());
+                }
+            }
+            KeyCode::Char('O') => {
+                if let Some(path) = app.selected_row().map(|row| row.path.clone()) {
+                    app.message = open_with_system(&path).err().map(|e| e.to_string());
+                }
+            }
+            _ => {}
+        }
+        // Follow the selection in the detail pane
+        if app.detail.is_some() {
+            app.detail = app.selected_name().map(&details);
+        }
+    }
+    Ok(())
+}
+
+/// Give the terminal back to the shell while `f` runs
+fn suspend<B: ratatui::backend::Backend>(
+    terminal: &mut Terminal<B>,
+    f: impl FnOnce(),
+) -> Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen)?;
+    f();
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    terminal.clear()?;
+    Ok(())
+}
+
+fn draw(frame: &mut ratatui::Frame, app: &mut App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(frame.size());
+    let table_area = if let Some(detail) = &app.detail {
+        let panes = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(chunks[0]);
+        let title = app.selected_name().unwrap_or_default().to_string();
+        let lines = detail
+            .iter()
+            .map(|line| Line::from(line.as_str()))
+            .collect::<Vec<_>>();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::default().borders(Borders::TOP).title(title)),
+            panes[1],
+        );
+        panes[0]
+    } else {
+        chunks[0]
+    };
+
+    let visible_rows = app.visible_rows();
+    let footer = app.footer();
+    let table_rows = &app.rows;
+    // Columns are as wide as their widest content
+    let widths = app
+        .headers
+        .iter()
+        .enumerate()
+        .map(|(column, header)| {
+            let widest = visible_rows
+                .iter()
+                .map(|&i| table_rows[i].cells[column].0.chars().count())
+                .max()
+                .unwrap_or(0);
+            Constraint::Length(widest.max(header.len()) as u16)
+        })
+        .collect::<Vec<_>>();
+    let rows = visible_rows.iter().map(|&i| {
+        Row::new(table_rows[i].cells.iter().map(|(content, color)| {
+            let style = color.map_or_else(Style::default, |color| Style::default().fg(color));
+            Cell::from(content.as_str()).style(style)
+        }))
+    });
+    let header = Row::new(app.headers.iter().map(String::as_str))
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let table = Table::new(rows, widths)
+        .header(header)
+        .column_spacing(3)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(table, table_area, &mut app.state);
+    frame.render_widget(Paragraph::new(Line::from(footer)), chunks[1]);
+}